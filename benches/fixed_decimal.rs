@@ -0,0 +1,206 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use num::{CheckedMul, CheckedSub, Integer};
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+use txdemo::fixed_decimal::{FixedDecimal, ParseOptions};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let values: Vec<FixedDecimal<u64, 4>> = (0..1000u64)
+        .map(|i| FixedDecimal::from_fractions(i * 7919 * 104729))
+        .collect();
+    let values = values.as_slice();
+    let legacy_values: Vec<LegacyDecimal<u64, 4>> = values
+        .iter()
+        .map(|value| LegacyDecimal(*value.fractions()))
+        .collect();
+    let legacy_values = legacy_values.as_slice();
+    for (value, legacy) in values.iter().zip(legacy_values) {
+        assert_eq!(value.to_string(), legacy.to_string());
+    }
+
+    let mut group = c.benchmark_group("fixed_decimal_format");
+    group.bench_function("legacy", |b| {
+        let mut out = String::new();
+        b.iter(|| {
+            for value in legacy_values {
+                out.clear();
+                write!(out, "{}", value).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+    group.bench_function("display", |b| {
+        let mut out = String::new();
+        b.iter(|| {
+            for value in values {
+                out.clear();
+                write!(out, "{}", value).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+    group.bench_function("write_to", |b| {
+        let mut buf = [0u8; 32];
+        b.iter(|| {
+            for value in values {
+                black_box(value.write_to(&mut buf));
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("fixed_decimal_serialize");
+    group.bench_function("legacy", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            for value in legacy_values {
+                out.clear();
+                serde_json::to_writer(&mut out, value).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+    group.bench_function("serialize", |b| {
+        let mut out = Vec::new();
+        b.iter(|| {
+            for value in values {
+                out.clear();
+                serde_json::to_writer(&mut out, value).unwrap();
+                black_box(&out);
+            }
+        })
+    });
+    group.finish();
+
+    let inputs: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    let inputs = inputs.as_slice();
+    let options = ParseOptions::new();
+
+    let mut group = c.benchmark_group("fixed_decimal_parse");
+    // The lexer used by `parse_with` is generic over the backing type: it serves as a
+    // reference for the `u64` fast path used by `from_str`.
+    group.bench_function("parse_with", |b| {
+        b.iter(|| {
+            for input in inputs {
+                black_box(FixedDecimal::<u64, 4>::parse_with(input, &options).unwrap());
+            }
+        })
+    });
+    group.bench_function("from_str", |b| {
+        b.iter(|| {
+            for input in inputs {
+                black_box(FixedDecimal::<u64, 4>::from_str(input).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Reference copy of the `Display` and `Serialize` implementations used
+/// before the stack buffer formatter.
+#[derive(Copy, Clone)]
+struct LegacyDecimal<T: Integer, const PRECISION: u8>(T);
+
+impl<T, const PRECISION: u8> LegacyDecimal<T, PRECISION>
+where
+    T: Integer + CheckedMul + From<u8>,
+{
+    fn fractions_per_unit() -> Option<T> {
+        let ten: T = 10.into();
+        let mut fractions = T::one();
+        for _ in 0..PRECISION {
+            fractions = fractions.checked_mul(&ten)?;
+        }
+        Some(fractions)
+    }
+}
+
+impl<T, const PRECISION: u8> Display for LegacyDecimal<T, PRECISION>
+where
+    T: Integer + Display + CheckedMul + CheckedSub + From<u8> + Clone,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if PRECISION == 0 {
+            return self.0.fmt(f);
+        }
+
+        let (sign, abs) = if self.0 < T::zero() {
+            ("-", T::zero().checked_sub(&self.0))
+        } else {
+            ("", Some(self.0.clone()))
+        };
+
+        match abs {
+            Some(abs) => {
+                let (int, frac) = match Self::fractions_per_unit() {
+                    None => (T::zero(), abs),
+                    Some(fpu) => abs.div_rem(&fpu),
+                };
+
+                write!(
+                    f,
+                    "{}{}.{:0>precision$}",
+                    sign,
+                    int,
+                    frac,
+                    precision = usize::from(PRECISION)
+                )
+            }
+            None => {
+                // Failed to compute absolute value (e.g. -128i8)
+                // Fall back to manual printing...
+                let zero = T::zero();
+                let one = T::one();
+                let ten = T::from(10);
+                let mut n = self.0.clone();
+                // Digits from right to left
+                let mut digits: Vec<T> = Vec::new();
+                while n != zero {
+                    let (q, r) = n.div_mod_floor(&ten);
+                    let digit = if q < zero && r != zero {
+                        // Fix values after div_floor on negative dividend
+                        n = q.add(one.clone());
+                        ten.checked_sub(&r).expect("cannot represent digit")
+                    } else {
+                        n = q;
+                        r
+                    };
+                    digits.push(digit);
+                }
+                write!(f, "{}", sign)?;
+                let digit_count = usize::max(digits.len(), usize::from(PRECISION) + 1);
+                let separator_index = digit_count - usize::from(PRECISION);
+                for i in 0..digit_count {
+                    if i == separator_index {
+                        write!(f, ".")?;
+                    }
+                    let digit = digit_count
+                        .checked_sub(i + 1)
+                        .and_then(|idx| digits.get(idx));
+                    if let Some(d) = digit {
+                        write!(f, "{}", d)?;
+                    } else {
+                        write!(f, "0")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T, const PRECISION: u8> Serialize for LegacyDecimal<T, PRECISION>
+where
+    T: Integer + Display + CheckedMul + CheckedSub + From<u8> + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,16 +1,146 @@
-use crate::core::{cmd, Account, AccountBalance, ClientId, Command, Transaction, TransactionId};
+use crate::core::{
+    cmd, Account, AccountBalance, AccountStatus, AssetId, BalanceUpdateError, ClientId, Command,
+    LockReason, Timestamp, Transaction, TransactionId, UnsignedAssetCount,
+};
+use crate::fee::{FeeKind, FeeSchedule, FeeSource, FeeTransaction, InsufficientFeeFundsPolicy};
+use crate::fixed_decimal::CheckedSum;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::Duration;
 use thiserror::Error;
 
-/// How to handle disputes related to withdrawal transactions.
-pub enum WithdrawalDisputePolicy {
-    /// Ignore the dispute if it relates to a withdrawal transaction.
-    Deny,
-    /// Allow the dispute only if the amount is less than the available assets.
-    /// (Allows to always seize the account and recover the refund in case of
-    /// fraudulent chargeback)
-    IfMoreAvailableThanDisputed,
+/// Rules deciding which disputes are accepted and how they are settled.
+///
+/// The hooks are called once the generic checks pass (claimant, locks, amounts,
+/// dispute window), before any update. Disputes resolved automatically after
+/// the resolution deadline also go through [DisputePolicy::can_resolve].
+///
+/// # Example
+///
+/// ```
+/// use txdemo::account_service::{
+///     DisputeContext, DisputePolicy, IfMoreAvailableThanDisputed, MemAccountService,
+/// };
+/// use txdemo::account_service::DisputeError;
+/// use txdemo::core::{cmd, AssetId, ClientId, TransactionId, TransactionMeta, UnsignedAssetCount};
+/// use txdemo::fixed;
+///
+/// /// Allow at most one open dispute per client.
+/// struct SingleOpenDispute;
+///
+/// impl DisputePolicy for SingleOpenDispute {
+///     fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError> {
+///         if ctx.open_disputes > 0 {
+///             return Err(DisputeError::DeniedByPolicy(format!(
+///                 "client #{} already has an open dispute",
+///                 ctx.claimant
+///             )));
+///         }
+///         IfMoreAvailableThanDisputed.can_dispute(ctx)
+///     }
+/// }
+///
+/// let mut service = MemAccountService::new(Box::new(SingleOpenDispute));
+/// for id in [1, 2] {
+///     service.submit_deposit(cmd::Deposit(TransactionMeta {
+///         id: TransactionId::new(id),
+///         client: ClientId::new(1),
+///         asset: AssetId::DEFAULT,
+///         amount: fixed!(UnsignedAssetCount, "1.0"),
+///         timestamp: None,
+///     })).unwrap();
+/// }
+/// let dispute = |tx| cmd::Dispute {
+///     client: ClientId::new(1),
+///     tx: TransactionId::new(tx),
+///     amount: None,
+///     timestamp: None,
+/// };
+/// assert!(service.submit_dispute(dispute(1)).is_ok());
+/// assert!(matches!(
+///     service.submit_dispute(dispute(2)),
+///     Err(DisputeError::DeniedByPolicy(_))
+/// ));
+/// ```
+pub trait DisputePolicy {
+    /// Check if a dispute can be filed.
+    fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError>;
+
+    /// Check if a dispute can be resolved (default: always).
+    ///
+    /// This is also checked before resolving a dispute automatically once its
+    /// resolution deadline is reached: if it fails, the dispute stays open and
+    /// the check is repeated for each later command, until the policy allows it
+    /// or the dispute is settled manually.
+    fn can_resolve(&self, _ctx: &DisputeContext) -> Result<(), ResolveError> {
+        Ok(())
+    }
+
+    /// Decide what happens to the claimant account following a chargeback
+    /// (default: lock it).
+    fn on_chargeback(&self, _ctx: &DisputeContext) -> ChargebackAction {
+        ChargebackAction::Lock
+    }
+}
+
+/// Information about a dispute command, passed to the [DisputePolicy] hooks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DisputeContext {
+    /// Disputed transaction.
+    pub tx: Transaction,
+    /// Client issuing the command (the owner of the transaction).
+    pub claimant: ClientId,
+    /// Current balance of the account holding the disputed assets (see
+    /// [Transaction::holder]), in the asset of the transaction.
+    pub balance: AccountBalance,
+    /// Amount disputed, resolved or charged back by the command.
+    pub amount: UnsignedAssetCount,
+    /// Number of transactions of the claimant currently disputed, including
+    /// the disputed transaction if it is already disputed.
+    pub open_disputes: usize,
+    /// Number of commands submitted since the transaction.
+    pub age: u64,
+}
+
+/// Action to apply to the claimant account following a chargeback.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChargebackAction {
+    /// Lock the account until an operator unlocks it.
+    Lock,
+    /// Keep the current status of the account.
+    KeepStatus,
+}
+
+/// Allow disputes only if the amount is less than the available assets.
+/// (Allows to always seize the account and recover the refund in case of
+/// fraudulent chargeback)
+///
+/// This is the default policy.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct IfMoreAvailableThanDisputed;
+
+impl DisputePolicy for IfMoreAvailableThanDisputed {
+    fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError> {
+        if ctx.balance.can_spend(ctx.amount) {
+            Ok(())
+        } else {
+            Err(DisputeError::InsufficientAssets)
+        }
+    }
+}
+
+/// Deny all the disputes related to withdrawal transactions, other disputes
+/// follow [IfMoreAvailableThanDisputed].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DenyWithdrawalDisputes;
+
+impl DisputePolicy for DenyWithdrawalDisputes {
+    fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError> {
+        match ctx.tx {
+            Transaction::Withdrawal(_) => Err(DisputeError::WithdrawalDisputeDenied),
+            _ => IfMoreAvailableThanDisputed.can_dispute(ctx),
+        }
+    }
 }
 
 /// In-memory account service.
@@ -21,21 +151,27 @@ pub enum WithdrawalDisputePolicy {
 /// # Example
 ///
 /// ```
-/// use txdemo::account_service::{MemAccountService, WithdrawalDisputePolicy};
-/// use txdemo::core::{cmd, ClientId, TransactionMeta, TransactionId, Account, AccountBalance, UnsignedAssetCount};
+/// use txdemo::account_service::MemAccountService;
+/// use txdemo::core::{cmd, AccountStatus, AssetId, ClientId, TransactionMeta, TransactionId, Account, AccountBalance, UnsignedAssetCount};
+/// use std::collections::BTreeMap;
+/// use txdemo::fixed;
 ///
 /// let mut service = MemAccountService::default();
 ///
 /// service.submit_deposit(cmd::Deposit(TransactionMeta {
 ///     id: TransactionId::new(1),
 ///     client: ClientId::new(1),
-///     amount: UnsignedAssetCount::new("1.2345".parse().unwrap()),
+///     asset: AssetId::DEFAULT,
+///     amount: fixed!(UnsignedAssetCount, "1.2345"),
+///     timestamp: None,
 /// })).unwrap();
 ///
 /// service.submit_deposit(cmd::Deposit(TransactionMeta {
 ///     id: TransactionId::new(2),
 ///     client: ClientId::new(1),
-///     amount: UnsignedAssetCount::new("1.1111".parse().unwrap()),
+///     asset: AssetId::DEFAULT,
+///     amount: fixed!(UnsignedAssetCount, "1.1111"),
+///     timestamp: None,
 /// })).unwrap();
 ///
 /// // ... submit more commands
@@ -44,17 +180,18 @@ pub enum WithdrawalDisputePolicy {
 /// let expected = vec![
 ///     Account {
 ///         client: ClientId::new(1),
-///         locked: false,
-///         balance: AccountBalance::new_with("2.3456".parse().unwrap(), "0".parse().unwrap()).unwrap(),
+///         status: AccountStatus::Active,
+///         balances: BTreeMap::from([(
+///             AssetId::DEFAULT,
+///             AccountBalance::new_with(fixed!(UnsignedAssetCount, "2.3456"), fixed!(UnsignedAssetCount, "0")).unwrap(),
+///         )]),
 ///     }
 /// ];
 /// assert_eq!(accounts, expected);
 /// ```
 pub struct MemAccountService {
-    /// Policy to use for withdrawal disputes.
-    ///
-    /// See [WithdrawalDisputePolicy]
-    withdrawal_dispute_policy: WithdrawalDisputePolicy,
+    /// Rules for disputes, see [DisputePolicy].
+    dispute_policy: Box<dyn DisputePolicy>,
     /// All the received transactions.
     ///
     /// If multiple transactions with the same id are submitted, only the first
@@ -73,87 +210,301 @@ pub struct MemAccountService {
     ///
     /// The id of the account matches the corresponding key in the hashmap.
     accounts: HashMap<ClientId, MemAccount>,
+    /// Credit limits configured for each client, see [MemAccountService::set_credit_limit].
+    ///
+    /// They are applied to the accounts when they are created.
+    credit_limits: HashMap<ClientId, UnsignedAssetCount>,
+    /// Ids allowed to issue operator commands (`unlock`, `freeze`, `close`, `fee`).
+    operators: HashSet<ClientId>,
+    /// Maximum age of a transaction when it is disputed, if any.
+    dispute_window: Option<Duration>,
+    /// Maximum duration of a dispute before it is resolved automatically, if any.
+    resolution_deadline: Option<Duration>,
+    /// Latest timestamp seen in the submitted commands.
+    now: Option<Timestamp>,
+    /// Resolution deadlines of the disputes, ordered by deadline.
+    ///
+    /// Entries are not removed when a dispute is settled manually: expired
+    /// entries are checked against the current state of the transaction.
+    dispute_deadlines: BTreeSet<(Timestamp, TransactionId)>,
+    /// Fees charged automatically, see [FeeSchedule].
+    fee_schedule: FeeSchedule,
+    /// All the fees charged so far.
+    fee_transactions: Vec<FeeTransaction>,
+    /// Number of commands submitted so far.
+    command_count: u64,
+    /// Number of disputed transactions of each client.
+    ///
+    /// Clients without any disputed transaction are omitted.
+    open_disputes: HashMap<ClientId, usize>,
 }
 
 /// A transaction with its current state.
 ///
-/// See [TransactionState] for the possibile states and their meaning.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Disputes may only apply to a part of the transaction amount, so the amount
+/// is split in portions with their own state. See [TransactionState] for the
+/// possibile states and their meaning.
+#[derive(Clone, Debug, Eq, PartialEq)]
 struct TransactionWithState {
     /// Transaction options
     tx: Transaction,
-    /// Current state of the transaction. See [TransactionState].
+    /// Number of commands submitted when the transaction was received.
+    seq: u64,
+    /// Portions of the transaction amount, with their current state.
+    ///
+    /// ## Invariant
+    ///
+    /// The amounts of the portions sum up to the transaction amount. Empty
+    /// portions are removed, and there is at most one `Valid` and one
+    /// `Rejected` portion. Disputed portions are kept in the order of the
+    /// disputes.
+    portions: Vec<Portion>,
+}
+
+/// A portion of a transaction amount, see [TransactionWithState].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Portion {
+    amount: UnsignedAssetCount,
     state: TransactionState,
+    /// Time when the dispute is resolved automatically, if any.
+    ///
+    /// Always `None` if the portion is not disputed.
+    resolve_deadline: Option<Timestamp>,
 }
 
 impl TransactionWithState {
     /// Create a new [TransactionWithState] starting in the `Valid` state.
-    pub const fn valid(tx: Transaction) -> Self {
-        Self {
-            tx,
-            state: TransactionState::Valid,
-        }
+    pub fn valid(tx: Transaction, seq: u64) -> Self {
+        Self::with_state(tx, seq, TransactionState::Valid)
     }
 
     /// Create a new [TransactionWithState] starting in the `Rejected` state.
-    pub const fn rejected(tx: Transaction) -> Self {
-        Self {
+    pub fn rejected(tx: Transaction, seq: u64) -> Self {
+        Self::with_state(tx, seq, TransactionState::Rejected)
+    }
+
+    fn with_state(tx: Transaction, seq: u64, state: TransactionState) -> Self {
+        let mut tx_with_state = Self {
             tx,
-            state: TransactionState::Rejected,
+            seq,
+            portions: vec![Portion {
+                amount: tx.amount(),
+                state,
+                resolve_deadline: None,
+            }],
+        };
+        tx_with_state.normalize();
+        tx_with_state
+    }
+
+    /// Check if a part of the transaction is currently disputed.
+    pub fn is_disputed(&self) -> bool {
+        self.amount_in(TransactionState::Disputed) != UnsignedAssetCount::default()
+    }
+
+    /// Get the total amount of the portions in the provided state.
+    pub fn amount_in(&self, state: TransactionState) -> UnsignedAssetCount {
+        UnsignedAssetCount::checked_sum(
+            self.portions
+                .iter()
+                .filter(|portion| portion.state == state)
+                .map(|portion| portion.amount),
+        )
+        .expect("portions should never exceed the transaction amount")
+    }
+
+    /// Move `amount` from the portions in the state `from` (oldest first) to a
+    /// new portion in the state `to`.
+    ///
+    /// The caller must check that `amount` does not exceed `self.amount_in(from)`.
+    pub fn move_amount(
+        &mut self,
+        amount: UnsignedAssetCount,
+        from: TransactionState,
+        to: TransactionState,
+        resolve_deadline: Option<Timestamp>,
+    ) {
+        debug_assert!(amount <= self.amount_in(from));
+        let mut remaining = amount;
+        for portion in self.portions.iter_mut().filter(|p| p.state == from) {
+            let taken = std::cmp::min(portion.amount, remaining);
+            portion.amount = portion
+                .amount
+                .checked_sub(taken)
+                .expect("taken amount is at most the portion amount");
+            remaining = remaining
+                .checked_sub(taken)
+                .expect("taken amount is at most the remaining amount");
+        }
+        self.portions.push(Portion {
+            amount,
+            state: to,
+            resolve_deadline,
+        });
+        self.normalize();
+    }
+
+    /// Get the total amount of the disputed portions whose deadline is not after `now`.
+    pub fn expired_amount(&self, now: Timestamp) -> UnsignedAssetCount {
+        UnsignedAssetCount::checked_sum(
+            self.portions
+                .iter()
+                .filter(|portion| portion.is_expired(now))
+                .map(|portion| portion.amount),
+        )
+        .expect("portions should never exceed the transaction amount")
+    }
+
+    /// Resolve the disputed portions whose deadline is not after `now`.
+    pub fn resolve_expired(&mut self, now: Timestamp) {
+        for portion in self.portions.iter_mut().filter(|p| p.is_expired(now)) {
+            portion.state = TransactionState::Valid;
+            portion.resolve_deadline = None;
+        }
+        self.normalize();
+    }
+
+    /// Restore the invariants of `portions`: drop empty portions and merge the
+    /// `Valid` and `Rejected` portions.
+    fn normalize(&mut self) {
+        let valid = self.amount_in(TransactionState::Valid);
+        let rejected = self.amount_in(TransactionState::Rejected);
+        let zero = UnsignedAssetCount::default();
+        self.portions
+            .retain(|p| p.state == TransactionState::Disputed && p.amount != zero);
+        for (amount, state) in [
+            (valid, TransactionState::Valid),
+            (rejected, TransactionState::Rejected),
+        ] {
+            if amount != zero {
+                self.portions.push(Portion {
+                    amount,
+                    state,
+                    resolve_deadline: None,
+                });
+            }
         }
     }
 }
 
-/// The current state of a transaction.
+impl Portion {
+    fn is_expired(&self, now: Timestamp) -> bool {
+        self.state == TransactionState::Disputed
+            && self
+                .resolve_deadline
+                .map_or(false, |deadline| deadline <= now)
+    }
+}
+
+/// The current state of a portion of a transaction.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum TransactionState {
-    /// The transaction is currently valid and its effect is realized.
+    /// The portion is currently valid and its effect is realized.
     ///
-    /// The transaction may become `Disputed` if a dispute is claimed.
+    /// The portion may become `Disputed` if a dispute is claimed.
     Valid,
-    /// The transaction is disputed: the corresponding assets are held.
+    /// The portion is disputed: the corresponding assets are held.
     ///
     /// A transaction dispute can only be claimed by the account owner.
     ///
-    /// The transaction can either become `Valid` again following a `resolve` by
+    /// The portion can either become `Valid` again following a `resolve` by
     /// the owner or be definitely rejected (and its effects reverted)
     /// following a `chargeback`.
     Disputed,
-    /// The transaction was rejected because of insufficient or following a chargeback.
+    /// The portion was rejected because of insufficient or following a chargeback.
     ///
-    /// Once rejected, a transaction stays in the rejected state.
+    /// Once rejected, a portion stays in the rejected state.
     Rejected,
 }
 
-/// An
+/// An account, with one balance per asset.
+///
+/// Locking applies to the whole account, regardless of the asset.
 struct MemAccount {
     id: ClientId,
-    locked: bool,
-    balance: AccountBalance,
+    status: AccountStatus,
+    /// Balance for each asset used by the account.
+    ///
+    /// Balances are created automatically when the asset is first credited.
+    balances: BTreeMap<AssetId, AccountBalance>,
+    /// Credit limit of all the balances, see [AccountBalance::credit_limit].
+    credit_limit: UnsignedAssetCount,
 }
 
 impl MemAccount {
     pub fn new(id: ClientId) -> Self {
         Self {
             id,
-            locked: false,
-            balance: AccountBalance::new(),
+            status: AccountStatus::Active,
+            balances: BTreeMap::new(),
+            credit_limit: UnsignedAssetCount::default(),
+        }
+    }
+
+    /// Get the current balance for the provided asset (empty if the asset was never used)
+    pub fn balance(&self, asset: AssetId) -> AccountBalance {
+        self.balances
+            .get(&asset)
+            .copied()
+            .unwrap_or_else(|| empty_balance(self.credit_limit))
+    }
+
+    /// Get or create the balance for the provided asset
+    pub fn balance_mut(&mut self, asset: AssetId) -> &mut AccountBalance {
+        let credit_limit = self.credit_limit;
+        self.balances
+            .entry(asset)
+            .or_insert_with(|| empty_balance(credit_limit))
+    }
+
+    /// Change the credit limit of all the balances, atomically.
+    pub fn set_credit_limit(
+        &mut self,
+        limit: UnsignedAssetCount,
+    ) -> Result<(), BalanceUpdateError> {
+        // Check that the limit is valid for the balances created later
+        AccountBalance::new().set_credit_limit(limit)?;
+        let mut new_balances = self.balances.clone();
+        for balance in new_balances.values_mut() {
+            balance.set_credit_limit(limit)?;
         }
+        self.balances = new_balances;
+        self.credit_limit = limit;
+        Ok(())
     }
 }
 
+/// Create an empty balance with the provided credit limit.
+fn empty_balance(credit_limit: UnsignedAssetCount) -> AccountBalance {
+    let mut balance = AccountBalance::new();
+    balance
+        .set_credit_limit(credit_limit)
+        .expect("credit limits are validated when set");
+    balance
+}
+
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum SubmitError {
     #[error("deposit command failed")]
     Deposit(#[from] DepositError),
     #[error("withdrawal command failed")]
     Withdrawal(#[from] WithdrawalError),
+    #[error("transfer command failed")]
+    Transfer(#[from] TransferError),
     #[error("dispute command failed")]
     Dispute(#[from] DisputeError),
     #[error("resolve command failed")]
     Resolve(#[from] ResolveError),
     #[error("chargeback command failed")]
     Chargeback(#[from] ChargebackError),
+    #[error("unlock command failed")]
+    Unlock(#[from] UnlockError),
+    #[error("freeze command failed")]
+    Freeze(#[from] FreezeError),
+    #[error("close command failed")]
+    Close(#[from] CloseError),
+    #[error("fee command failed")]
+    Fee(#[from] FeeError),
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -164,6 +515,8 @@ pub enum DepositError {
     Locked,
     #[error("failed to update the account balance due to an overflow or underflow")]
     BalanceUpdateError,
+    #[error("insufficient available assets to pay the deposit fee")]
+    InsufficientAssetsForFee,
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -176,6 +529,26 @@ pub enum WithdrawalError {
     BalanceUpdateError,
     #[error("insufficient available assets to complete the withdrawal")]
     InsufficientAssets,
+    #[error("insufficient available assets to pay the withdrawal fee")]
+    InsufficientAssetsForFee,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum TransferError {
+    #[error("multiple different transactions have the same transaction id")]
+    TransactionIdConflict,
+    #[error("the sender and recipient accounts are the same")]
+    SameAccount,
+    #[error("locked sender account")]
+    SenderLocked,
+    #[error("locked recipient account")]
+    RecipientLocked,
+    #[error("failed to update the account balances due to an overflow or underflow")]
+    BalanceUpdateError,
+    #[error("insufficient available assets to complete the transfer")]
+    InsufficientAssets,
+    #[error("insufficient available assets to pay the transfer fee")]
+    InsufficientAssetsForFee,
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -194,6 +567,12 @@ pub enum DisputeError {
     WithdrawalDisputeDenied,
     #[error("insufficient available assets to file the dispute")]
     InsufficientAssets,
+    #[error("transaction #{} is too old to be disputed", .0)]
+    WindowExpired(TransactionId),
+    #[error("the disputed amount exceeds the undisputed amount of transaction #{}", .0)]
+    ExceedsUndisputed(TransactionId),
+    #[error("the dispute is denied by the dispute policy: {}", .0)]
+    DeniedByPolicy(String),
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -208,6 +587,10 @@ pub enum ResolveError {
     Locked,
     #[error("failed to update the account balance due to an overflow or underflow")]
     BalanceUpdateError,
+    #[error("the resolved amount exceeds the disputed amount of transaction #{}", .0)]
+    ExceedsDisputed(TransactionId),
+    #[error("the resolution is denied by the dispute policy: {}", .0)]
+    DeniedByPolicy(String),
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -222,44 +605,187 @@ pub enum ChargebackError {
     Locked,
     #[error("failed to update the account balance due to an overflow or underflow")]
     BalanceUpdateError,
+    #[error("the charged back amount exceeds the disputed amount of transaction #{}", .0)]
+    ExceedsDisputed(TransactionId),
+    #[error("insufficient available assets to pay the chargeback fee")]
+    InsufficientAssetsForFee,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum UnlockError {
+    #[error("only operators are allowed to unlock accounts: issuer: #{}", .0)]
+    NotOperator(ClientId),
+    #[error("the client account is closed, it cannot be unlocked")]
+    Closed,
+    #[error("unknown client: #{}", .0)]
+    UnknownClient(ClientId),
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum FreezeError {
+    #[error("only operators are allowed to freeze accounts: issuer: #{}", .0)]
+    NotOperator(ClientId),
+    #[error("the client account is already closed")]
+    Closed,
+    #[error("unknown client: #{}", .0)]
+    UnknownClient(ClientId),
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum CloseError {
+    #[error("only operators are allowed to close accounts: issuer: #{}", .0)]
+    NotOperator(ClientId),
+    #[error("the client account still holds assets or owes fees")]
+    NonEmptyBalance,
+    #[error("unknown client: #{}", .0)]
+    UnknownClient(ClientId),
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum CreditLimitError {
+    #[error("the client account already uses more credit than the new limit")]
+    ExceedsLimit,
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum FeeError {
+    #[error("only operators are allowed to charge fees: issuer: #{}", .0)]
+    NotOperator(ClientId),
+    #[error("the client account is closed")]
+    Closed,
+    #[error("insufficient available assets to pay the fee")]
+    InsufficientAssets,
+    #[error("failed to update the account balance due to an overflow or underflow")]
+    BalanceUpdateError,
+    #[error("unknown client: #{}", .0)]
+    UnknownClient(ClientId),
 }
 
 impl MemAccountService {
-    pub fn new(withdrawal_dispute_policy: WithdrawalDisputePolicy) -> Self {
+    pub fn new(dispute_policy: Box<dyn DisputePolicy>) -> Self {
         Self {
-            withdrawal_dispute_policy,
+            dispute_policy,
             transactions: HashMap::new(),
             accounts: HashMap::new(),
+            credit_limits: HashMap::new(),
+            operators: HashSet::new(),
+            dispute_window: None,
+            resolution_deadline: None,
+            now: None,
+            dispute_deadlines: BTreeSet::new(),
+            fee_schedule: FeeSchedule::default(),
+            fee_transactions: Vec::new(),
+            command_count: 0,
+            open_disputes: HashMap::new(),
         }
     }
 
+    /// Reject disputes against transactions older than `window` (default: no limit).
+    ///
+    /// The age of a transaction is computed from its timestamp and the timestamp of
+    /// the dispute (or the latest timestamp seen). If any of them is unknown, the
+    /// dispute is allowed.
+    pub fn set_dispute_window(&mut self, window: Option<Duration>) {
+        self.dispute_window = window;
+    }
+
+    /// Resolve disputes automatically once they are older than `deadline` (default: never).
+    ///
+    /// Stale disputes are resolved when a command with a later timestamp is
+    /// submitted, if the [DisputePolicy] allows it; a denied resolution is tried
+    /// again on the next later command. Disputes without a timestamp are never
+    /// resolved automatically. Disputes on locked accounts wait until the account
+    /// is unlocked, and are resolved by the next later command.
+    pub fn set_resolution_deadline(&mut self, deadline: Option<Duration>) {
+        self.resolution_deadline = deadline;
+    }
+
+    /// Charge fees automatically with the provided schedule (default: no fees).
+    pub fn set_fee_schedule(&mut self, schedule: FeeSchedule) {
+        self.fee_schedule = schedule;
+    }
+
+    /// Allow the `available` assets of `client` to go down to `-limit`, in every asset
+    /// (default: no overdraft).
+    ///
+    /// Withdrawals, transfers and disputes may then use the credit. No account
+    /// is created: the limit applies once the account is created. Lowering the
+    /// limit fails if the account already uses more credit.
+    pub fn set_credit_limit(
+        &mut self,
+        client: ClientId,
+        limit: UnsignedAssetCount,
+    ) -> Result<(), CreditLimitError> {
+        match self.accounts.get_mut(&client) {
+            Some(account) => account.set_credit_limit(limit),
+            // Check that the limit is valid for the account created later
+            None => MemAccount::new(client).set_credit_limit(limit),
+        }
+        .map_err(|_| CreditLimitError::ExceedsLimit)?;
+        self.credit_limits.insert(client, limit);
+        Ok(())
+    }
+
+    /// Allow `operator` to issue operator commands (`unlock`, `freeze`, `close`, `fee`).
+    pub fn add_operator(&mut self, operator: ClientId) {
+        self.operators.insert(operator);
+    }
+
     pub fn submit(&mut self, cmd: Command) -> Result<(), SubmitError> {
         match cmd {
             Command::Deposit(cmd) => self.submit_deposit(cmd)?,
             Command::Withdrawal(cmd) => self.submit_withdrawal(cmd)?,
+            Command::Transfer(cmd) => self.submit_transfer(cmd)?,
             Command::Dispute(cmd) => self.submit_dispute(cmd)?,
             Command::Resolve(cmd) => self.submit_resolve(cmd)?,
             Command::Chargeback(cmd) => self.submit_chargeback(cmd)?,
+            Command::Unlock(cmd) => self.submit_unlock(cmd)?,
+            Command::Freeze(cmd) => self.submit_freeze(cmd)?,
+            Command::Close(cmd) => self.submit_close(cmd)?,
+            Command::Fee(cmd) => self.submit_fee(cmd)?,
         }
         Ok(())
     }
 
     pub fn submit_deposit(&mut self, cmd: cmd::Deposit) -> Result<(), DepositError> {
         let cmd = cmd.0;
+        self.start_command(cmd.timestamp);
         let tx = cmd.to_deposit_tx();
-        let account = upsert_account(&mut self.accounts, cmd.client);
+        let fee_schedule = &self.fee_schedule;
+        let fee_transactions = &mut self.fee_transactions;
+        let account = upsert_account(&mut self.accounts, &self.credit_limits, cmd.client);
         let res = upsert_tx(
             &mut self.transactions,
             tx,
+            self.command_count,
             || -> Result<(), DepositError> {
-                if account.locked {
+                if account.status.is_locked() {
                     return Err(DepositError::Locked);
                 };
 
-                account
-                    .balance
+                let mut balance = account.balance(cmd.asset);
+                balance
                     .inc_available(cmd.amount)
                     .map_err(|_| DepositError::BalanceUpdateError)?;
+                let fee = charge_scheduled_fee(
+                    fee_schedule,
+                    FeeKind::Deposit,
+                    cmd.id,
+                    cmd.client,
+                    cmd.asset,
+                    cmd.amount,
+                    &mut balance,
+                )
+                .map_err(|e| match e {
+                    ChargeFeeError::Overflow => DepositError::BalanceUpdateError,
+                    ChargeFeeError::InsufficientAssets => DepositError::InsufficientAssetsForFee,
+                })?;
+                balance
+                    .collect_fee_debt()
+                    .map_err(|_| DepositError::BalanceUpdateError)?;
+
+                *account.balance_mut(cmd.asset) = balance;
+                fee_transactions.extend(fee);
                 Ok(())
             },
         );
@@ -272,24 +798,44 @@ impl MemAccountService {
 
     pub fn submit_withdrawal(&mut self, cmd: cmd::Withdrawal) -> Result<(), WithdrawalError> {
         let cmd = cmd.0;
+        self.start_command(cmd.timestamp);
         let tx = cmd.to_withdrawal_tx();
-        let account = upsert_account(&mut self.accounts, cmd.client);
+        let fee_schedule = &self.fee_schedule;
+        let fee_transactions = &mut self.fee_transactions;
+        let account = upsert_account(&mut self.accounts, &self.credit_limits, cmd.client);
         let res = upsert_tx(
             &mut self.transactions,
             tx,
+            self.command_count,
             || -> Result<(), WithdrawalError> {
-                if account.locked {
+                if account.status.is_locked() {
                     return Err(WithdrawalError::Locked);
                 };
 
-                if account.balance.available() < cmd.amount {
+                if !account.balance(cmd.asset).can_spend(cmd.amount) {
                     return Err(WithdrawalError::InsufficientAssets);
                 }
 
-                account
-                    .balance
+                let mut balance = account.balance(cmd.asset);
+                balance
                     .dec_available(cmd.amount)
                     .map_err(|_| WithdrawalError::BalanceUpdateError)?;
+                let fee = charge_scheduled_fee(
+                    fee_schedule,
+                    FeeKind::Withdrawal,
+                    cmd.id,
+                    cmd.client,
+                    cmd.asset,
+                    cmd.amount,
+                    &mut balance,
+                )
+                .map_err(|e| match e {
+                    ChargeFeeError::Overflow => WithdrawalError::BalanceUpdateError,
+                    ChargeFeeError::InsufficientAssets => WithdrawalError::InsufficientAssetsForFee,
+                })?;
+
+                *account.balance_mut(cmd.asset) = balance;
+                fee_transactions.extend(fee);
                 Ok(())
             },
         );
@@ -300,188 +846,655 @@ impl MemAccountService {
         })
     }
 
+    pub fn submit_transfer(&mut self, cmd: cmd::Transfer) -> Result<(), TransferError> {
+        let cmd::Transfer { meta, to } = cmd;
+        self.start_command(meta.timestamp);
+        let tx = meta.to_transfer_tx(to);
+        let fee_schedule = &self.fee_schedule;
+        let fee_transactions = &mut self.fee_transactions;
+        let accounts = &mut self.accounts;
+        let credit_limits = &self.credit_limits;
+        let res = upsert_tx(
+            &mut self.transactions,
+            tx,
+            self.command_count,
+            || -> Result<(), TransferError> {
+                if meta.client == to {
+                    return Err(TransferError::SameAccount);
+                }
+
+                let sender = upsert_account(accounts, credit_limits, meta.client);
+                if sender.status.is_locked() {
+                    return Err(TransferError::SenderLocked);
+                }
+                if !sender.balance(meta.asset).can_spend(meta.amount) {
+                    return Err(TransferError::InsufficientAssets);
+                }
+                let mut sender_balance = sender.balance(meta.asset);
+                sender_balance
+                    .dec_available(meta.amount)
+                    .map_err(|_| TransferError::BalanceUpdateError)?;
+                let fee = charge_scheduled_fee(
+                    fee_schedule,
+                    FeeKind::Transfer,
+                    meta.id,
+                    meta.client,
+                    meta.asset,
+                    meta.amount,
+                    &mut sender_balance,
+                )
+                .map_err(|e| match e {
+                    ChargeFeeError::Overflow => TransferError::BalanceUpdateError,
+                    ChargeFeeError::InsufficientAssets => TransferError::InsufficientAssetsForFee,
+                })?;
+
+                // The recipient account is only created once the transfer succeeds
+                let new_recipient;
+                let recipient = match accounts.get(&to) {
+                    Some(recipient) => recipient,
+                    None => {
+                        new_recipient = new_account(credit_limits, to);
+                        &new_recipient
+                    }
+                };
+                if recipient.status.is_locked() {
+                    return Err(TransferError::RecipientLocked);
+                }
+                let mut recipient_balance = recipient.balance(meta.asset);
+                recipient_balance
+                    .inc_available(meta.amount)
+                    .map_err(|_| TransferError::BalanceUpdateError)?;
+                recipient_balance
+                    .collect_fee_debt()
+                    .map_err(|_| TransferError::BalanceUpdateError)?;
+
+                // Both updates are valid: apply them
+                *upsert_account(accounts, credit_limits, to).balance_mut(meta.asset) =
+                    recipient_balance;
+                *upsert_account(accounts, credit_limits, meta.client).balance_mut(meta.asset) =
+                    sender_balance;
+                fee_transactions.extend(fee);
+                Ok(())
+            },
+        );
+
+        res.map_err(|e| match e {
+            UpsertTxError::Conflict => TransferError::TransactionIdConflict,
+            UpsertTxError::Custom(e) => e,
+        })
+    }
+
     pub fn submit_dispute(&mut self, cmd: cmd::Dispute) -> Result<(), DisputeError> {
+        self.start_command(cmd.timestamp);
         let tx = self
             .transactions
             .get_mut(&cmd.tx)
             .ok_or(DisputeError::NotFound(cmd.tx))?;
 
-        let account = upsert_account(&mut self.accounts, tx.tx.client());
-
-        if cmd.client != account.id {
+        let owner = tx.tx.client();
+        if cmd.client != owner {
             return Err(DisputeError::InvalidClaimant {
-                owner: account.id,
+                owner,
                 claimant: cmd.client,
             });
         }
 
-        if account.locked {
+        if is_locked(&self.accounts, &tx.tx) {
             return Err(DisputeError::Locked);
         }
 
-        match tx.state {
-            TransactionState::Rejected => return Err(DisputeError::AlreadyRejected(cmd.tx)),
-            TransactionState::Disputed => {
-                // Claiming a dispute against the same transaction again is a no-op
+        let undisputed_amount = tx.amount_in(TransactionState::Valid);
+        if undisputed_amount == UnsignedAssetCount::default() {
+            return if tx.amount_in(TransactionState::Disputed) == UnsignedAssetCount::default() {
+                Err(DisputeError::AlreadyRejected(cmd.tx))
+            } else {
+                // Claiming a dispute against an already disputed transaction is a no-op
+                Ok(())
+            };
+        }
+        let disputed_amount = match cmd.amount {
+            Some(amount) if amount > undisputed_amount => {
+                return Err(DisputeError::ExceedsUndisputed(cmd.tx))
             }
-            TransactionState::Valid => {
-                let disputed_amount = tx.tx.amount();
-                let has_more_available_than_disputed =
-                    account.balance.available() >= disputed_amount;
-
-                // Check dispute validity
-                match tx.tx {
-                    Transaction::Deposit(_) => {
-                        if !has_more_available_than_disputed {
-                            return Err(DisputeError::InsufficientAssets);
-                        }
-                    }
-                    Transaction::Withdrawal(_) => match self.withdrawal_dispute_policy {
-                        WithdrawalDisputePolicy::Deny => {
-                            return Err(DisputeError::WithdrawalDisputeDenied)
-                        }
-                        WithdrawalDisputePolicy::IfMoreAvailableThanDisputed => {
-                            if !has_more_available_than_disputed {
-                                return Err(DisputeError::InsufficientAssets);
-                            }
-                        }
-                    },
-                };
+            Some(amount) => amount,
+            None => undisputed_amount,
+        };
+        if disputed_amount == UnsignedAssetCount::default() {
+            return Ok(());
+        }
 
-                // At this point the dispute is valid: apply it
-                account
-                    .balance
-                    .move_available_to_held(disputed_amount)
-                    .map_err(|_| DisputeError::BalanceUpdateError)?;
-                tx.state = TransactionState::Disputed;
+        let now = cmd.timestamp.or(self.now);
+        if let (Some(window), Some(tx_time), Some(now)) =
+            (self.dispute_window, tx.tx.timestamp(), now)
+        {
+            let is_expired = tx_time
+                .checked_add(window)
+                .map_or(false, |window_end| now > window_end);
+            if is_expired {
+                return Err(DisputeError::WindowExpired(cmd.tx));
             }
+        }
+
+        let account = upsert_account(&mut self.accounts, &self.credit_limits, tx.tx.holder());
+        let ctx = dispute_context(
+            tx,
+            cmd.client,
+            account.balance(tx.tx.asset()),
+            disputed_amount,
+            &self.open_disputes,
+            self.command_count,
+        );
+        self.dispute_policy.can_dispute(&ctx)?;
+
+        // At this point the dispute is valid: apply it
+        account
+            .balance_mut(tx.tx.asset())
+            .move_available_to_held(disputed_amount)
+            .map_err(|_| DisputeError::BalanceUpdateError)?;
+        let resolve_deadline = match (self.resolution_deadline, now) {
+            (Some(deadline), Some(now)) => now.checked_add(deadline),
+            _ => None,
         };
+        let was_disputed = tx.is_disputed();
+        tx.move_amount(
+            disputed_amount,
+            TransactionState::Valid,
+            TransactionState::Disputed,
+            resolve_deadline,
+        );
+        update_open_disputes(&mut self.open_disputes, owner, was_disputed, true);
+        if let Some(resolve_deadline) = resolve_deadline {
+            self.dispute_deadlines.insert((resolve_deadline, cmd.tx));
+        }
 
         Ok(())
     }
 
     pub fn submit_resolve(&mut self, cmd: cmd::Resolve) -> Result<(), ResolveError> {
+        self.start_command(cmd.timestamp);
         let tx = self
             .transactions
             .get_mut(&cmd.tx)
             .ok_or(ResolveError::NotFound(cmd.tx))?;
 
-        let account = upsert_account(&mut self.accounts, tx.tx.client());
-
-        if cmd.client != account.id {
+        let owner = tx.tx.client();
+        if cmd.client != owner {
             return Err(ResolveError::InvalidClaimant {
-                owner: account.id,
+                owner,
                 claimant: cmd.client,
             });
         }
 
-        if account.locked {
+        if is_locked(&self.accounts, &tx.tx) {
             return Err(ResolveError::Locked);
         }
 
-        match tx.state {
-            TransactionState::Rejected => return Err(ResolveError::AlreadyRejected(cmd.tx)),
-            TransactionState::Valid => {
+        let disputed_amount = tx.amount_in(TransactionState::Disputed);
+        if disputed_amount == UnsignedAssetCount::default() {
+            return if tx.amount_in(TransactionState::Valid) == UnsignedAssetCount::default() {
+                Err(ResolveError::AlreadyRejected(cmd.tx))
+            } else {
                 // Resolving a dispute against an already valid transaction is a no-op
+                Ok(())
+            };
+        }
+        let resolved_amount = match cmd.amount {
+            Some(amount) if amount > disputed_amount => {
+                return Err(ResolveError::ExceedsDisputed(cmd.tx))
             }
-            TransactionState::Disputed => {
-                let disputed_amount = tx.tx.amount();
-
-                // Un-freeze the held assets by moving them back to the `available` state.
-                account
-                    .balance
-                    .move_held_to_available(disputed_amount)
-                    .map_err(|_| ResolveError::BalanceUpdateError)?;
-                tx.state = TransactionState::Valid;
-            }
+            Some(amount) => amount,
+            None => disputed_amount,
         };
 
+        let account = upsert_account(&mut self.accounts, &self.credit_limits, tx.tx.holder());
+        let ctx = dispute_context(
+            tx,
+            cmd.client,
+            account.balance(tx.tx.asset()),
+            resolved_amount,
+            &self.open_disputes,
+            self.command_count,
+        );
+        self.dispute_policy.can_resolve(&ctx)?;
+
+        // Un-freeze the held assets by moving them back to the `available` state.
+        account
+            .balance_mut(tx.tx.asset())
+            .move_held_to_available(resolved_amount)
+            .map_err(|_| ResolveError::BalanceUpdateError)?;
+        tx.move_amount(
+            resolved_amount,
+            TransactionState::Disputed,
+            TransactionState::Valid,
+            None,
+        );
+        update_open_disputes(&mut self.open_disputes, owner, true, tx.is_disputed());
+
         Ok(())
     }
 
     pub fn submit_chargeback(&mut self, cmd: cmd::Chargeback) -> Result<(), ChargebackError> {
+        self.start_command(cmd.timestamp);
         let tx = self
             .transactions
             .get_mut(&cmd.tx)
             .ok_or(ChargebackError::NotFound(cmd.tx))?;
 
-        let account = upsert_account(&mut self.accounts, tx.tx.client());
-
-        if cmd.client != account.id {
+        let owner = tx.tx.client();
+        if cmd.client != owner {
             return Err(ChargebackError::InvalidClaimant {
-                owner: account.id,
+                owner,
                 claimant: cmd.client,
             });
         }
 
-        if account.locked {
+        if is_locked(&self.accounts, &tx.tx) {
             return Err(ChargebackError::Locked);
         }
 
-        match tx.state {
-            TransactionState::Rejected => {
+        let disputed_amount = tx.amount_in(TransactionState::Disputed);
+        if disputed_amount == UnsignedAssetCount::default() {
+            return if tx.amount_in(TransactionState::Valid) == UnsignedAssetCount::default() {
                 // Chargebacking an already rejected command is a no-op
+                Ok(())
+            } else {
+                Err(ChargebackError::NonDisputed(cmd.tx))
+            };
+        }
+        let charged_back_amount = match cmd.amount {
+            Some(amount) if amount > disputed_amount => {
+                return Err(ChargebackError::ExceedsDisputed(cmd.tx))
             }
-            TransactionState::Valid => return Err(ChargebackError::NonDisputed(cmd.tx)),
-            TransactionState::Disputed => {
-                let disputed_amount = tx.tx.amount();
-
-                let (new_available, new_held) = match &tx.tx {
-                    Transaction::Deposit(_) => {
-                        // Remove the disputed amount from the held assets, no change to `available`:
-                        let new_held = account
-                            .balance
-                            .held()
-                            .checked_sub(disputed_amount)
-                            .ok_or(ChargebackError::BalanceUpdateError)?;
-                        (account.balance.available(), new_held)
-                    }
-                    Transaction::Withdrawal(_) => {
-                        // Move the held disputed amount to `available`
-                        let new_held = account
-                            .balance
-                            .held()
-                            .checked_sub(disputed_amount)
-                            .ok_or(ChargebackError::BalanceUpdateError)?;
-                        let new_available = account
-                            .balance
-                            .available()
-                            .checked_add(disputed_amount)
-                            .ok_or(ChargebackError::BalanceUpdateError)?;
-                        // Then refund the withdrawn assets
-                        let new_available = new_available
-                            .checked_add(disputed_amount)
-                            .ok_or(ChargebackError::BalanceUpdateError)?;
-                        (new_available, new_held)
-                    }
-                };
-
-                account
-                    .balance
-                    .update(new_available, new_held)
+            Some(amount) => amount,
+            None => disputed_amount,
+        };
+        let asset = tx.tx.asset();
+        // Transfers are reverted by refunding the sender
+        let mut refund = match tx.tx {
+            Transaction::Transfer { meta, .. } => {
+                let mut sender_balance =
+                    upsert_account(&mut self.accounts, &self.credit_limits, meta.client)
+                        .balance(asset);
+                sender_balance
+                    .inc_available(charged_back_amount)
                     .map_err(|_| ChargebackError::BalanceUpdateError)?;
-                account.locked = true;
-                tx.state = TransactionState::Rejected;
+                Some(sender_balance)
             }
+            _ => None,
         };
 
+        let account = upsert_account(&mut self.accounts, &self.credit_limits, tx.tx.holder());
+        let mut balance = account.balance(asset);
+        let ctx = dispute_context(
+            tx,
+            cmd.client,
+            balance,
+            charged_back_amount,
+            &self.open_disputes,
+            self.command_count,
+        );
+        let action = self.dispute_policy.on_chargeback(&ctx);
+
+        match &tx.tx {
+            Transaction::Deposit(_) | Transaction::Transfer { .. } => {
+                // Remove the disputed amount from the held assets, no change to `available`:
+                balance.dec_held(charged_back_amount)
+            }
+            Transaction::Withdrawal(_) => {
+                // Move the held disputed amount to `available`, then refund the withdrawn assets
+                balance
+                    .move_held_to_available(charged_back_amount)
+                    .and_then(|()| balance.inc_available(charged_back_amount))
+            }
+        }
+        .map_err(|_| ChargebackError::BalanceUpdateError)?;
+
+        // The fee is charged to the claimant, once refunded
+        let fee = charge_scheduled_fee(
+            &self.fee_schedule,
+            FeeKind::Chargeback,
+            cmd.tx,
+            owner,
+            asset,
+            charged_back_amount,
+            refund.as_mut().unwrap_or(&mut balance),
+        )
+        .map_err(|e| match e {
+            ChargeFeeError::Overflow => ChargebackError::BalanceUpdateError,
+            ChargeFeeError::InsufficientAssets => ChargebackError::InsufficientAssetsForFee,
+        })?;
+
+        *account.balance_mut(asset) = balance;
+        let claimant = match refund {
+            Some(sender_balance) => {
+                // The recipient is not locked: only the claimant is
+                let sender = upsert_account(&mut self.accounts, &self.credit_limits, owner);
+                *sender.balance_mut(asset) = sender_balance;
+                sender
+            }
+            None => account,
+        };
+        if action == ChargebackAction::Lock {
+            claimant.status = AccountStatus::Locked {
+                reason: LockReason::Chargeback,
+                by_tx: cmd.tx,
+            };
+        }
+        self.fee_transactions.extend(fee);
+        tx.move_amount(
+            charged_back_amount,
+            TransactionState::Disputed,
+            TransactionState::Rejected,
+            None,
+        );
+        update_open_disputes(&mut self.open_disputes, owner, true, tx.is_disputed());
+
+        Ok(())
+    }
+
+    pub fn submit_unlock(&mut self, cmd: cmd::Unlock) -> Result<(), UnlockError> {
+        self.start_command(cmd.timestamp);
+        if !self.operators.contains(&cmd.operator) {
+            return Err(UnlockError::NotOperator(cmd.operator));
+        }
+
+        let account = self
+            .accounts
+            .get_mut(&cmd.client)
+            .ok_or(UnlockError::UnknownClient(cmd.client))?;
+
+        if account.status == AccountStatus::Closed {
+            return Err(UnlockError::Closed);
+        }
+
+        // Unlocking an account which is not locked is a no-op
+        account.status = AccountStatus::Active;
+        Ok(())
+    }
+
+    pub fn submit_freeze(&mut self, cmd: cmd::Freeze) -> Result<(), FreezeError> {
+        self.start_command(cmd.timestamp);
+        if !self.operators.contains(&cmd.operator) {
+            return Err(FreezeError::NotOperator(cmd.operator));
+        }
+
+        let account = self
+            .accounts
+            .get_mut(&cmd.client)
+            .ok_or(FreezeError::UnknownClient(cmd.client))?;
+
+        match account.status {
+            AccountStatus::Closed => return Err(FreezeError::Closed),
+            AccountStatus::Active => account.status = AccountStatus::Frozen,
+            AccountStatus::Locked { .. } | AccountStatus::Frozen => {
+                // Freezing an already locked account is a no-op
+            }
+        }
         Ok(())
     }
 
-    pub fn get_all_accounts(&self) -> MemAccountIter {
+    pub fn submit_close(&mut self, cmd: cmd::Close) -> Result<(), CloseError> {
+        self.start_command(cmd.timestamp);
+        if !self.operators.contains(&cmd.operator) {
+            return Err(CloseError::NotOperator(cmd.operator));
+        }
+
+        let account = self
+            .accounts
+            .get_mut(&cmd.client)
+            .ok_or(CloseError::UnknownClient(cmd.client))?;
+
+        let is_empty = account.balances.values().all(|balance| balance.is_empty());
+        if !is_empty {
+            return Err(CloseError::NonEmptyBalance);
+        }
+
+        // Closing an already closed account is a no-op
+        account.status = AccountStatus::Closed;
+        Ok(())
+    }
+
+    pub fn submit_fee(&mut self, cmd: cmd::Fee) -> Result<(), FeeError> {
+        self.start_command(cmd.timestamp);
+        if !self.operators.contains(&cmd.operator) {
+            return Err(FeeError::NotOperator(cmd.operator));
+        }
+
+        let allow_debt = self.fee_schedule.insufficient_funds == InsufficientFeeFundsPolicy::Debt;
+        let account = self
+            .accounts
+            .get_mut(&cmd.client)
+            .ok_or(FeeError::UnknownClient(cmd.client))?;
+
+        if account.status == AccountStatus::Closed {
+            return Err(FeeError::Closed);
+        }
+
+        // Fees are charged regardless of the other locks
+        let debt = account
+            .balance_mut(cmd.asset)
+            .pay_fee(cmd.amount, allow_debt)
+            .map_err(|_| {
+                if allow_debt {
+                    FeeError::BalanceUpdateError
+                } else {
+                    FeeError::InsufficientAssets
+                }
+            })?;
+        self.fee_transactions.push(FeeTransaction {
+            client: cmd.client,
+            asset: cmd.asset,
+            amount: cmd.amount,
+            debt,
+            source: FeeSource::Command {
+                operator: cmd.operator,
+                parent: cmd.tx,
+            },
+        });
+        Ok(())
+    }
+
+    /// Count a new command issued at `timestamp`, see [Self::advance_clock].
+    fn start_command(&mut self, timestamp: Option<Timestamp>) {
+        self.command_count = self.command_count.saturating_add(1);
+        self.advance_clock(timestamp);
+    }
+
+    /// Record that a command was issued at `timestamp`, and resolve the stale disputes.
+    fn advance_clock(&mut self, timestamp: Option<Timestamp>) {
+        let now = match timestamp {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+        if self.now.map_or(false, |prev| prev >= now) {
+            // Commands are not always received in order: never go back in time
+            return;
+        }
+        self.now = Some(now);
+
+        let expired: Vec<(Timestamp, TransactionId)> = self
+            .dispute_deadlines
+            .iter()
+            .take_while(|&&(deadline, _)| deadline <= now)
+            .copied()
+            .collect();
+        for entry in expired {
+            let tx = match self.transactions.get_mut(&entry.1) {
+                Some(tx) => tx,
+                None => {
+                    self.dispute_deadlines.remove(&entry);
+                    continue;
+                }
+            };
+            let expired_amount = tx.expired_amount(now);
+            if expired_amount == UnsignedAssetCount::default() {
+                // Already settled by a resolve or chargeback
+                self.dispute_deadlines.remove(&entry);
+                continue;
+            }
+            if is_locked(&self.accounts, &tx.tx) {
+                // Retried on the next clock advance once the account is unlocked
+                continue;
+            }
+            let account = upsert_account(&mut self.accounts, &self.credit_limits, tx.tx.holder());
+            let ctx = dispute_context(
+                tx,
+                tx.tx.client(),
+                account.balance(tx.tx.asset()),
+                expired_amount,
+                &self.open_disputes,
+                self.command_count,
+            );
+            if self.dispute_policy.can_resolve(&ctx).is_err() {
+                // The policy is asked again on the next clock advance
+                continue;
+            }
+            let resolved = account
+                .balance_mut(tx.tx.asset())
+                .move_held_to_available(expired_amount);
+            debug_assert!(
+                resolved.is_ok(),
+                "failed to release the held funds of a disputed transaction"
+            );
+            if resolved.is_ok() {
+                self.dispute_deadlines.remove(&entry);
+                tx.resolve_expired(now);
+                let is_disputed = tx.is_disputed();
+                update_open_disputes(&mut self.open_disputes, tx.tx.client(), true, is_disputed);
+            }
+        }
+    }
+
+    /// Get all the fees charged so far, in the order they were charged.
+    pub fn get_fee_transactions(&self) -> &[FeeTransaction] {
+        &self.fee_transactions
+    }
+
+    pub fn get_all_accounts(&self) -> MemAccountIter<'_> {
         let inner = self.accounts.values();
         MemAccountIter { inner }
     }
 }
 
 /// Get or create the account for the provided client
-fn upsert_account(
-    accounts: &mut HashMap<ClientId, MemAccount>,
+fn upsert_account<'a>(
+    accounts: &'a mut HashMap<ClientId, MemAccount>,
+    credit_limits: &HashMap<ClientId, UnsignedAssetCount>,
     client: ClientId,
-) -> &mut MemAccount {
+) -> &'a mut MemAccount {
     accounts
         .entry(client)
-        .or_insert_with(|| MemAccount::new(client))
+        .or_insert_with(|| new_account(credit_limits, client))
+}
+
+/// Create an empty account with the credit limit configured for the client
+fn new_account(
+    credit_limits: &HashMap<ClientId, UnsignedAssetCount>,
+    client: ClientId,
+) -> MemAccount {
+    let mut account = MemAccount::new(client);
+    if let Some(&limit) = credit_limits.get(&client) {
+        account
+            .set_credit_limit(limit)
+            .expect("credit limits should be checked when configured");
+    }
+    account
+}
+
+/// Check if the owner or the holder of the transaction assets is locked
+///
+/// Missing accounts are not locked, and are not created.
+fn is_locked(accounts: &HashMap<ClientId, MemAccount>, tx: &Transaction) -> bool {
+    let is_account_locked = |client| {
+        accounts
+            .get(&client)
+            .map_or(false, |account: &MemAccount| account.status.is_locked())
+    };
+    is_account_locked(tx.client()) || is_account_locked(tx.holder())
+}
+
+/// Build the context passed to the [DisputePolicy] hooks.
+fn dispute_context(
+    tx: &TransactionWithState,
+    claimant: ClientId,
+    balance: AccountBalance,
+    amount: UnsignedAssetCount,
+    open_disputes: &HashMap<ClientId, usize>,
+    command_count: u64,
+) -> DisputeContext {
+    DisputeContext {
+        tx: tx.tx,
+        claimant,
+        balance,
+        amount,
+        open_disputes: open_disputes.get(&claimant).copied().unwrap_or_default(),
+        age: command_count.saturating_sub(tx.seq),
+    }
+}
+
+/// Update the count of disputed transactions of `client` after a state change.
+fn update_open_disputes(
+    open_disputes: &mut HashMap<ClientId, usize>,
+    client: ClientId,
+    was_disputed: bool,
+    is_disputed: bool,
+) {
+    match (was_disputed, is_disputed) {
+        (false, true) => *open_disputes.entry(client).or_default() += 1,
+        (true, false) => {
+            if let Entry::Occupied(mut count) = open_disputes.entry(client) {
+                *count.get_mut() -= 1;
+                if *count.get() == 0 {
+                    count.remove();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+enum ChargeFeeError {
+    /// Computing or paying the fee causes an overflow or underflow.
+    Overflow,
+    /// The fee exceeds the available assets and fee debts are not allowed.
+    InsufficientAssets,
+}
+
+/// Charge the scheduled fee for a command to `balance`.
+///
+/// `balance` is the balance of `client` once the command is applied, the
+/// caller is responsible for storing it. Returns the fee transaction to record
+/// if the command succeeds (if the fee is not zero).
+fn charge_scheduled_fee(
+    schedule: &FeeSchedule,
+    kind: FeeKind,
+    parent: TransactionId,
+    client: ClientId,
+    asset: AssetId,
+    amount: UnsignedAssetCount,
+    balance: &mut AccountBalance,
+) -> Result<Option<FeeTransaction>, ChargeFeeError> {
+    let fee = schedule
+        .compute(kind, amount)
+        .map_err(|_| ChargeFeeError::Overflow)?;
+    if fee == UnsignedAssetCount::default() {
+        return Ok(None);
+    }
+    let allow_debt = schedule.insufficient_funds == InsufficientFeeFundsPolicy::Debt;
+    let debt = balance.pay_fee(fee, allow_debt).map_err(|_| {
+        if allow_debt {
+            ChargeFeeError::Overflow
+        } else {
+            ChargeFeeError::InsufficientAssets
+        }
+    })?;
+    Ok(Some(FeeTransaction {
+        client,
+        asset,
+        amount: fee,
+        debt,
+        source: FeeSource::Schedule { kind, parent },
+    }))
 }
 
 enum UpsertTxError<E> {
@@ -501,6 +1514,7 @@ enum UpsertTxError<E> {
 fn upsert_tx<F, E>(
     transactions: &mut HashMap<TransactionId, TransactionWithState>,
     tx: Transaction,
+    seq: u64,
     handler: F,
 ) -> Result<(), UpsertTxError<E>>
 where
@@ -509,10 +1523,11 @@ where
     let tx_entry = transactions.entry(tx.id());
     let tx_entry = match tx_entry {
         Entry::Occupied(tx_entry) => {
-            return if tx_entry.get().tx != tx {
+            return if !tx_entry.get().tx.is_retry_of(&tx) {
                 Err(UpsertTxError::Conflict)
             } else {
                 // Same id, with same fields (probably an idempotent retry, ignore)
+                // The timestamp of the first command is kept
                 Ok(())
             };
         }
@@ -521,11 +1536,11 @@ where
     let handler_res = handler();
     match handler_res {
         Ok(()) => {
-            tx_entry.insert(TransactionWithState::valid(tx));
+            tx_entry.insert(TransactionWithState::valid(tx, seq));
             Ok(())
         }
         Err(e) => {
-            tx_entry.insert(TransactionWithState::rejected(tx));
+            tx_entry.insert(TransactionWithState::rejected(tx, seq));
             Err(UpsertTxError::Custom(e))
         }
     }
@@ -533,7 +1548,7 @@ where
 
 impl Default for MemAccountService {
     fn default() -> Self {
-        Self::new(WithdrawalDisputePolicy::IfMoreAvailableThanDisputed)
+        Self::new(Box::new(IfMoreAvailableThanDisputed))
     }
 }
 
@@ -548,8 +1563,169 @@ impl<'a> Iterator for MemAccountIter<'a> {
         let account = self.inner.next()?;
         Some(Account {
             client: account.id,
-            balance: account.balance,
-            locked: account.locked,
+            balances: account.balances.clone(),
+            status: account.status,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ChargebackAction, DisputeContext, DisputeError, DisputePolicy, IfMoreAvailableThanDisputed,
+        MemAccountService, ResolveError,
+    };
+    use crate::core::{
+        cmd, Account, AccountStatus, AssetId, ClientId, SignedAssetCount, Timestamp, TransactionId,
+        TransactionMeta, UnsignedAssetCount,
+    };
+    use std::time::Duration;
+
+    /// Keep the account active after chargebacks, and never settle disputes without an operator.
+    struct Lenient;
+
+    impl DisputePolicy for Lenient {
+        fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError> {
+            IfMoreAvailableThanDisputed.can_dispute(ctx)
+        }
+
+        fn can_resolve(&self, _ctx: &DisputeContext) -> Result<(), ResolveError> {
+            Err(ResolveError::DeniedByPolicy("manual review".to_string()))
+        }
+
+        fn on_chargeback(&self, _ctx: &DisputeContext) -> ChargebackAction {
+            ChargebackAction::KeepStatus
+        }
+    }
+
+    /// Only settle disputes on transactions older than `self.0` commands.
+    struct MinAge(u64);
+
+    impl DisputePolicy for MinAge {
+        fn can_dispute(&self, ctx: &DisputeContext) -> Result<(), DisputeError> {
+            IfMoreAvailableThanDisputed.can_dispute(ctx)
+        }
+
+        fn can_resolve(&self, ctx: &DisputeContext) -> Result<(), ResolveError> {
+            if ctx.age < self.0 {
+                return Err(ResolveError::DeniedByPolicy("too recent".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    fn deposit(service: &mut MemAccountService, id: u32, amount: UnsignedAssetCount, time: u64) {
+        service
+            .submit_deposit(cmd::Deposit(TransactionMeta {
+                id: TransactionId::new(id),
+                client: ClientId::new(1),
+                asset: AssetId::DEFAULT,
+                amount,
+                timestamp: Some(Timestamp::new(time)),
+            }))
+            .unwrap();
+    }
+
+    fn dispute(service: &mut MemAccountService, id: u32, time: u64) {
+        service
+            .submit_dispute(cmd::Dispute {
+                client: ClientId::new(1),
+                tx: TransactionId::new(id),
+                amount: None,
+                timestamp: Some(Timestamp::new(time)),
+            })
+            .unwrap();
+    }
+
+    fn account(service: &MemAccountService) -> Account {
+        service.get_all_accounts().next().unwrap()
+    }
+
+    #[test]
+    fn chargeback_keep_status() {
+        let mut service = MemAccountService::new(Box::new(Lenient));
+        deposit(&mut service, 1, crate::fixed!(UnsignedAssetCount, "10"), 0);
+        deposit(&mut service, 2, crate::fixed!(UnsignedAssetCount, "5"), 0);
+        dispute(&mut service, 1, 0);
+        service
+            .submit_chargeback(cmd::Chargeback {
+                client: ClientId::new(1),
+                tx: TransactionId::new(1),
+                amount: None,
+                timestamp: None,
+            })
+            .unwrap();
+        let account = account(&service);
+        assert_eq!(account.status, AccountStatus::Active);
+        let balance = account.balance(AssetId::DEFAULT);
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "5"));
+        assert_eq!(balance.held(), crate::fixed!(UnsignedAssetCount, "0"));
+        // The account is still usable
+        deposit(&mut service, 3, crate::fixed!(UnsignedAssetCount, "1"), 0);
+    }
+
+    #[test]
+    fn auto_resolution_checks_the_policy() {
+        let amount = crate::fixed!(UnsignedAssetCount, "10");
+        for (policy, held) in [
+            (
+                Box::new(IfMoreAvailableThanDisputed) as Box<dyn DisputePolicy>,
+                crate::fixed!(UnsignedAssetCount, "0"),
+            ),
+            (Box::new(Lenient), amount),
+        ] {
+            let mut service = MemAccountService::new(policy);
+            service.set_resolution_deadline(Some(Duration::from_secs(10)));
+            deposit(&mut service, 1, amount, 100);
+            dispute(&mut service, 1, 100);
+            deposit(&mut service, 2, crate::fixed!(UnsignedAssetCount, "1"), 200);
+            assert_eq!(account(&service).balance(AssetId::DEFAULT).held(), held);
+        }
+    }
+
+    #[test]
+    fn auto_resolution_retries_the_policy() {
+        let amount = crate::fixed!(UnsignedAssetCount, "10");
+        let mut service = MemAccountService::new(Box::new(MinAge(3)));
+        service.set_resolution_deadline(Some(Duration::from_secs(10)));
+        deposit(&mut service, 1, amount, 100);
+        dispute(&mut service, 1, 100);
+        deposit(&mut service, 2, crate::fixed!(UnsignedAssetCount, "1"), 200);
+        assert_eq!(account(&service).balance(AssetId::DEFAULT).held(), amount);
+        deposit(&mut service, 3, crate::fixed!(UnsignedAssetCount, "1"), 300);
+        let balance = account(&service).balance(AssetId::DEFAULT);
+        assert_eq!(balance.held(), crate::fixed!(UnsignedAssetCount, "0"));
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "12"));
+    }
+
+    #[test]
+    fn auto_resolution_waits_for_unlock() {
+        let amount = crate::fixed!(UnsignedAssetCount, "10");
+        let operator = ClientId::new(9);
+        let mut service = MemAccountService::new(Box::new(IfMoreAvailableThanDisputed));
+        service.add_operator(operator);
+        service.set_resolution_deadline(Some(Duration::from_secs(10)));
+        deposit(&mut service, 1, amount, 100);
+        dispute(&mut service, 1, 100);
+        service
+            .submit_freeze(cmd::Freeze {
+                operator,
+                client: ClientId::new(1),
+                timestamp: Some(Timestamp::new(105)),
+            })
+            .unwrap();
+        // The deadline is reached while the account is frozen
+        service
+            .submit_unlock(cmd::Unlock {
+                operator,
+                client: ClientId::new(1),
+                timestamp: Some(Timestamp::new(120)),
+            })
+            .unwrap();
+        assert_eq!(account(&service).balance(AssetId::DEFAULT).held(), amount);
+        deposit(&mut service, 2, crate::fixed!(UnsignedAssetCount, "1"), 130);
+        let balance = account(&service).balance(AssetId::DEFAULT);
+        assert_eq!(balance.held(), crate::fixed!(UnsignedAssetCount, "0"));
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "11"));
+    }
+}
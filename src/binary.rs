@@ -0,0 +1,583 @@
+//! Compact binary encoding for snapshots and journals.
+//!
+//! All the integers are encoded in little-endian order. The layouts are:
+//! - Fixed-width decimals ([FixedDecimal]): precision tag (`u8`), then the fractions with the
+//!   fixed width of the backing type.
+//! - Variable-length decimals ([VarintDecimal]): precision tag (`u8`), sign tag (`u8`, `0` for
+//!   unsigned backing types and `1` for signed backing types), then the fractions as a canonical
+//!   LEB128 varint (zigzag encoded for signed backing types).
+//! - Client ids ([ClientId]): `u64`, or with the `string-client-id` feature, the length (`u8`)
+//!   followed by the UTF-8 bytes of the id.
+//! - Transaction ids ([TransactionId]): `u32`.
+//! - Asset ids ([AssetId]): `u16`.
+//! - Timestamps ([Timestamp]): `u64`. Optional timestamps are prefixed with a presence tag (`u8`,
+//!   `0` if missing and `1` if present).
+//! - Transactions ([Transaction]): kind tag (`u8`, `0` for deposits, `1` for withdrawals and `2`
+//!   for transfers), then the transaction id, the client id, the asset id, the amount as a
+//!   variable-length decimal and the optional timestamp. Transfers end with the recipient id.
+//!
+//! Decoding is strict: values out of the range of the target type (including negative values
+//! decoded into unsigned types), mismatched precisions, overlong varints and unknown tags are
+//! rejected.
+
+use crate::core::{
+    AssetId, ClientId, Timestamp, Transaction, TransactionId, TransactionMeta, UnsignedAssetCount,
+};
+use crate::fixed_decimal::FixedDecimal;
+use std::convert::{TryFrom, TryInto};
+use thiserror::Error;
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    #[error("unexpected trailing bytes after the encoded value")]
+    TrailingBytes,
+    #[error("precision mismatch: expected {}, actual {}", .expected, .actual)]
+    PrecisionMismatch { expected: u8, actual: u8 },
+    #[error("decoded value is out of the range of the target type")]
+    OutOfRange,
+    #[error("invalid tag {}", .0)]
+    InvalidTag(u8),
+    #[error("non-canonical varint encoding")]
+    NonCanonicalVarint,
+}
+
+/// A value with a binary representation.
+pub trait BinaryEncode {
+    /// Append the binary representation of this value to `out`.
+    fn encode_into(&self, out: &mut Vec<u8>);
+}
+
+/// A value which can be read back from its binary representation.
+pub trait BinaryDecode: Sized {
+    /// Read a value from the start of `input`, and advance `input` past it.
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError>;
+
+    /// Read a value spanning the whole `input`.
+    fn decode(mut input: &[u8]) -> Result<Self, DecodeError> {
+        let value = Self::decode_from(&mut input)?;
+        if !input.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(value)
+    }
+}
+
+/// A fixed decimal value using the variable-length encoding.
+///
+/// Small values use fewer bytes than with the fixed-width encoding of [FixedDecimal].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct VarintDecimal<T: num::Integer, const PRECISION: u8>(pub FixedDecimal<T, PRECISION>);
+
+/// Read `N` bytes from the start of `input`.
+fn take<const N: usize>(input: &mut &[u8]) -> Result<[u8; N], DecodeError> {
+    if input.len() < N {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (head, tail) = input.split_at(N);
+    *input = tail;
+    Ok(head.try_into().expect("head should have exactly N bytes"))
+}
+
+/// Read the precision tag and check that it matches `PRECISION`.
+fn take_precision<const PRECISION: u8>(input: &mut &[u8]) -> Result<(), DecodeError> {
+    let [actual] = take::<1>(input)?;
+    if actual != PRECISION {
+        return Err(DecodeError::PrecisionMismatch {
+            expected: PRECISION,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Append `value` as a LEB128 varint.
+fn encode_varint(mut value: u128, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Read a LEB128 varint, rejecting overlong encodings.
+fn decode_varint(input: &mut &[u8]) -> Result<u128, DecodeError> {
+    let mut value: u128 = 0;
+    let mut shift: u32 = 0;
+    loop {
+        let [byte] = take::<1>(input)?;
+        let bits = u128::from(byte & 0x7f);
+        if shift >= 128 || (bits << shift) >> shift != bits {
+            return Err(DecodeError::OutOfRange);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            // A final zero byte only adds leading zeros, unless it is the only byte
+            if byte == 0 && shift > 0 {
+                return Err(DecodeError::NonCanonicalVarint);
+            }
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+const UNSIGNED_VARINT_TAG: u8 = 0;
+const SIGNED_VARINT_TAG: u8 = 1;
+
+/// Zigzag encoding: small magnitudes use few bytes regardless of the sign.
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+fn unzigzag(value: u128) -> i128 {
+    ((value >> 1) as i128) ^ -((value & 1) as i128)
+}
+
+/// Read the sign tag and the varint following it, as a signed value.
+///
+/// Unsigned varints above `i128::MAX` are returned as `Err(magnitude)` so unsigned targets can
+/// still decode them.
+fn decode_tagged_varint(input: &mut &[u8]) -> Result<Result<i128, u128>, DecodeError> {
+    let [tag] = take::<1>(input)?;
+    let signed = match tag {
+        UNSIGNED_VARINT_TAG => false,
+        SIGNED_VARINT_TAG => true,
+        tag => return Err(DecodeError::InvalidTag(tag)),
+    };
+    let value = decode_varint(input)?;
+    Ok(if signed {
+        Ok(unzigzag(value))
+    } else {
+        i128::try_from(value).map_err(|_| value)
+    })
+}
+
+macro_rules! impl_unsigned_decimal {
+    ($($typ:ty),+$(,)?) => {
+        $(
+            impl<const PRECISION: u8> BinaryEncode for FixedDecimal<$typ, PRECISION> {
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    out.push(PRECISION);
+                    out.extend_from_slice(&self.fractions().to_le_bytes());
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryDecode for FixedDecimal<$typ, PRECISION> {
+                fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    take_precision::<PRECISION>(input)?;
+                    let bytes = take::<{ std::mem::size_of::<$typ>() }>(input)?;
+                    Ok(Self::from_fractions(<$typ>::from_le_bytes(bytes)))
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryEncode for VarintDecimal<$typ, PRECISION> {
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    out.push(PRECISION);
+                    out.push(UNSIGNED_VARINT_TAG);
+                    encode_varint(u128::from(*self.0.fractions()), out);
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryDecode for VarintDecimal<$typ, PRECISION> {
+                fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    take_precision::<PRECISION>(input)?;
+                    let fractions = match decode_tagged_varint(input)? {
+                        Ok(value) => <$typ>::try_from(value).ok(),
+                        Err(value) => <$typ>::try_from(value).ok(),
+                    };
+                    let fractions = fractions.ok_or(DecodeError::OutOfRange)?;
+                    Ok(Self(FixedDecimal::from_fractions(fractions)))
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_signed_decimal {
+    ($($typ:ty),+$(,)?) => {
+        $(
+            impl<const PRECISION: u8> BinaryEncode for FixedDecimal<$typ, PRECISION> {
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    out.push(PRECISION);
+                    out.extend_from_slice(&self.fractions().to_le_bytes());
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryDecode for FixedDecimal<$typ, PRECISION> {
+                fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    take_precision::<PRECISION>(input)?;
+                    let bytes = take::<{ std::mem::size_of::<$typ>() }>(input)?;
+                    Ok(Self::from_fractions(<$typ>::from_le_bytes(bytes)))
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryEncode for VarintDecimal<$typ, PRECISION> {
+                fn encode_into(&self, out: &mut Vec<u8>) {
+                    out.push(PRECISION);
+                    out.push(SIGNED_VARINT_TAG);
+                    encode_varint(zigzag(i128::from(*self.0.fractions())), out);
+                }
+            }
+
+            impl<const PRECISION: u8> BinaryDecode for VarintDecimal<$typ, PRECISION> {
+                fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    take_precision::<PRECISION>(input)?;
+                    let value = decode_tagged_varint(input)?.map_err(|_| DecodeError::OutOfRange)?;
+                    let fractions = <$typ>::try_from(value).map_err(|_| DecodeError::OutOfRange)?;
+                    Ok(Self(FixedDecimal::from_fractions(fractions)))
+                }
+            }
+        )+
+    };
+}
+
+impl_unsigned_decimal!(u16, u32, u64, u128);
+impl_signed_decimal!(i16, i32, i64, i128);
+
+#[cfg(not(feature = "string-client-id"))]
+impl BinaryEncode for ClientId {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.get().to_le_bytes());
+    }
+}
+
+#[cfg(not(feature = "string-client-id"))]
+impl BinaryDecode for ClientId {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self::new(u64::from_le_bytes(take(input)?)))
+    }
+}
+
+#[cfg(feature = "string-client-id")]
+impl BinaryEncode for ClientId {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let id = self.string_id();
+        let id = id.as_str();
+        out.push(u8::try_from(id.len()).expect("string ids should fit in 255 bytes"));
+        out.extend_from_slice(id.as_bytes());
+    }
+}
+
+#[cfg(feature = "string-client-id")]
+impl BinaryDecode for ClientId {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let [len] = take::<1>(input)?;
+        let len = usize::from(len);
+        if input.len() < len {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (id, tail) = input.split_at(len);
+        *input = tail;
+        let id = std::str::from_utf8(id).map_err(|_| DecodeError::OutOfRange)?;
+        id.parse().map_err(|_| DecodeError::OutOfRange)
+    }
+}
+
+impl BinaryEncode for TransactionId {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.get().to_le_bytes());
+    }
+}
+
+impl BinaryDecode for TransactionId {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self::new(u32::from_le_bytes(take(input)?)))
+    }
+}
+
+impl BinaryEncode for AssetId {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.get().to_le_bytes());
+    }
+}
+
+impl BinaryDecode for AssetId {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self::new(u16::from_le_bytes(take(input)?)))
+    }
+}
+
+impl BinaryEncode for Timestamp {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.get().to_le_bytes());
+    }
+}
+
+impl BinaryDecode for Timestamp {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self::new(u64::from_le_bytes(take(input)?)))
+    }
+}
+
+impl<T: BinaryEncode> BinaryEncode for Option<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode_into(out);
+            }
+        }
+    }
+}
+
+impl<T: BinaryDecode> BinaryDecode for Option<T> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match take::<1>(input)? {
+            [0] => Ok(None),
+            [1] => T::decode_from(input).map(Some),
+            [tag] => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+impl BinaryEncode for UnsignedAssetCount {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        VarintDecimal(self.get()).encode_into(out)
+    }
+}
+
+impl BinaryDecode for UnsignedAssetCount {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        VarintDecimal::decode_from(input).map(|value| Self::new(value.0))
+    }
+}
+
+const DEPOSIT_TAG: u8 = 0;
+const WITHDRAWAL_TAG: u8 = 1;
+const TRANSFER_TAG: u8 = 2;
+
+impl BinaryEncode for Transaction {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let (tag, meta) = match self {
+            Self::Deposit(meta) => (DEPOSIT_TAG, meta),
+            Self::Withdrawal(meta) => (WITHDRAWAL_TAG, meta),
+            Self::Transfer { meta, .. } => (TRANSFER_TAG, meta),
+        };
+        out.push(tag);
+        meta.id.encode_into(out);
+        meta.client.encode_into(out);
+        meta.asset.encode_into(out);
+        meta.amount.encode_into(out);
+        meta.timestamp.encode_into(out);
+        if let Self::Transfer { to, .. } = self {
+            to.encode_into(out);
+        }
+    }
+}
+
+impl BinaryDecode for Transaction {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let [tag] = take::<1>(input)?;
+        if tag != DEPOSIT_TAG && tag != WITHDRAWAL_TAG && tag != TRANSFER_TAG {
+            return Err(DecodeError::InvalidTag(tag));
+        }
+        let meta = TransactionMeta {
+            id: TransactionId::decode_from(input)?,
+            client: ClientId::decode_from(input)?,
+            asset: AssetId::decode_from(input)?,
+            amount: UnsignedAssetCount::decode_from(input)?,
+            timestamp: Option::decode_from(input)?,
+        };
+        Ok(match tag {
+            DEPOSIT_TAG => meta.to_deposit_tx(),
+            WITHDRAWAL_TAG => meta.to_withdrawal_tx(),
+            _ => meta.to_transfer_tx(ClientId::decode_from(input)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary::{BinaryDecode, BinaryEncode, DecodeError, VarintDecimal};
+    use crate::core::{
+        AssetId, ClientId, Timestamp, Transaction, TransactionId, TransactionMeta,
+        UnsignedAssetCount,
+    };
+    use crate::fixed_decimal::FixedDecimal;
+
+    fn encode<T: BinaryEncode>(value: &T) -> Vec<u8> {
+        let mut out = Vec::new();
+        value.encode_into(&mut out);
+        out
+    }
+
+    #[test]
+    fn fixed_decimal_round_trip() {
+        let value = FixedDecimal::<u64, 4>::from_literal("1.2345");
+        let bytes = encode(&value);
+        assert_eq!(bytes, vec![4, 0x39, 0x30, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(FixedDecimal::<u64, 4>::decode(&bytes), Ok(value));
+    }
+
+    #[test]
+    fn fixed_decimal_precision_mismatch() {
+        let bytes = encode(&FixedDecimal::<u64, 4>::from_literal("1"));
+        assert_eq!(
+            FixedDecimal::<u64, 2>::decode(&bytes),
+            Err(DecodeError::PrecisionMismatch {
+                expected: 2,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn fixed_decimal_truncated() {
+        let bytes = encode(&FixedDecimal::<u64, 4>::from_literal("1"));
+        assert_eq!(
+            FixedDecimal::<u64, 4>::decode(&bytes[..5]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn fixed_decimal_trailing_bytes() {
+        let mut bytes = encode(&FixedDecimal::<u16, 4>::from_literal("1"));
+        bytes.push(0);
+        assert_eq!(
+            FixedDecimal::<u16, 4>::decode(&bytes),
+            Err(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let value = VarintDecimal(FixedDecimal::<u64, 4>::from_literal("0.0127"));
+        let bytes = encode(&value);
+        assert_eq!(bytes, vec![4, 0, 127]);
+        assert_eq!(VarintDecimal::<u64, 4>::decode(&bytes), Ok(value));
+        let max = VarintDecimal(FixedDecimal::<u64, 4>::from_fractions(u64::MAX));
+        assert_eq!(VarintDecimal::<u64, 4>::decode(&encode(&max)), Ok(max));
+    }
+
+    #[test]
+    fn varint_signed_round_trip() {
+        for fractions in [0, 1, -1, 63, -64, i64::MAX, i64::MIN] {
+            let value = VarintDecimal(FixedDecimal::<i64, 4>::from_fractions(fractions));
+            assert_eq!(VarintDecimal::<i64, 4>::decode(&encode(&value)), Ok(value));
+        }
+        let value = VarintDecimal(FixedDecimal::<i64, 4>::from_fractions(-1));
+        assert_eq!(encode(&value), vec![4, 1, 1]);
+    }
+
+    #[test]
+    fn varint_out_of_range() {
+        let large = VarintDecimal(FixedDecimal::<u128, 4>::from_fractions(
+            u128::from(u64::MAX) + 1,
+        ));
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&encode(&large)),
+            Err(DecodeError::OutOfRange)
+        );
+        let negative = VarintDecimal(FixedDecimal::<i64, 4>::from_fractions(-1));
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&encode(&negative)),
+            Err(DecodeError::OutOfRange)
+        );
+        let positive = VarintDecimal(FixedDecimal::<i64, 4>::from_fractions(1));
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&encode(&positive)),
+            Ok(VarintDecimal(FixedDecimal::from_fractions(1)))
+        );
+        let unsigned = VarintDecimal(FixedDecimal::<u128, 4>::from_fractions(u128::MAX));
+        assert_eq!(
+            VarintDecimal::<i128, 4>::decode(&encode(&unsigned)),
+            Err(DecodeError::OutOfRange)
+        );
+        // Varint longer than 128 bits
+        let mut bytes = vec![4, 0];
+        bytes.extend([0xff; 19]);
+        bytes.push(0x01);
+        assert_eq!(
+            VarintDecimal::<u128, 4>::decode(&bytes),
+            Err(DecodeError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn varint_non_canonical() {
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&[4, 0, 0x81, 0x00]),
+            Err(DecodeError::NonCanonicalVarint)
+        );
+        assert_eq!(
+            VarintDecimal::<i64, 4>::decode(&[4, 1, 0x80, 0x80, 0x00]),
+            Err(DecodeError::NonCanonicalVarint)
+        );
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&[4, 0, 0x00]),
+            Ok(VarintDecimal(FixedDecimal::from_fractions(0)))
+        );
+    }
+
+    #[test]
+    fn varint_invalid_sign_tag() {
+        assert_eq!(
+            VarintDecimal::<u64, 4>::decode(&[4, 2, 0]),
+            Err(DecodeError::InvalidTag(2))
+        );
+    }
+
+    #[test]
+    fn transaction_round_trip() {
+        let tx = Transaction::Withdrawal(TransactionMeta {
+            id: TransactionId::new(258),
+            client: ClientId::new(3),
+            asset: AssetId::new(840),
+            amount: crate::fixed!(UnsignedAssetCount, "1.5"),
+            timestamp: Some(Timestamp::new(1)),
+        });
+        let bytes = encode(&tx);
+        #[cfg(not(feature = "string-client-id"))]
+        assert_eq!(
+            bytes,
+            vec![
+                1, 2, 1, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0x48, 0x03, 4, 0, 0x98, 0x75, 1, 1, 0, 0, 0,
+                0, 0, 0, 0
+            ]
+        );
+        assert_eq!(Transaction::decode(&bytes), Ok(tx));
+    }
+
+    #[test]
+    fn client_id_round_trip() {
+        let id = ClientId::new(u64::MAX);
+        assert_eq!(ClientId::decode(&encode(&id)), Ok(id));
+    }
+
+    #[test]
+    fn transfer_round_trip() {
+        let tx = TransactionMeta {
+            id: TransactionId::new(1),
+            client: ClientId::new(2),
+            asset: AssetId::DEFAULT,
+            amount: crate::fixed!(UnsignedAssetCount, "3"),
+            timestamp: None,
+        }
+        .to_transfer_tx(ClientId::new(4));
+        assert_eq!(Transaction::decode(&encode(&tx)), Ok(tx));
+    }
+
+    #[test]
+    fn optional_invalid_tag() {
+        assert_eq!(
+            Option::<Timestamp>::decode(&[2, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::InvalidTag(2))
+        );
+    }
+
+    #[test]
+    fn transaction_invalid_tag() {
+        assert_eq!(
+            Transaction::decode(&[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0]),
+            Err(DecodeError::InvalidTag(3))
+        );
+    }
+}
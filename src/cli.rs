@@ -1,12 +1,17 @@
-use crate::account_service::{MemAccountService, WithdrawalDisputePolicy};
-use crate::core::{Account, ClientId};
-use crate::csv::{CsvAccountWriter, CsvCommandReader};
+use crate::account_service::{
+    DenyWithdrawalDisputes, DisputePolicy, IfMoreAvailableThanDisputed, MemAccountService,
+};
+use crate::core::{Account, AssetId, ClientId, UnsignedAssetCount};
+use crate::csv::{AccountColumns, CsvAccountWriter, CsvCommandReader};
+use crate::fee::{FeeKind, FeeRate, FeeSchedule, InsufficientFeeFundsPolicy};
 use clap::Clap;
 use exitcode::ExitCode;
 use std::ffi::OsString;
 use std::fs::File;
 use std::io;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::private::AsDynError;
 
 /// Execute a stream of commands against an in-memory account service.
@@ -19,12 +24,106 @@ pub struct CliArgs {
     /// Sort output accounts by client id (default: false)
     #[clap(long)]
     sort: bool,
+    /// Add the `asset` column to the output (default: only if an account holds an asset other
+    /// than the default one).
+    #[clap(long)]
+    asset_column: bool,
+    /// Add the `status` and `lock_reason` columns to the output (default: false).
+    #[clap(long)]
+    status_columns: bool,
+    /// Add the `fees` and `fee_debt` columns to the output (default: false).
+    #[clap(long)]
+    fee_columns: bool,
     /// Deny all disputes related to withdrawals (default: allow if the account has more available
     /// assets than the disputed amount).
     #[clap(long)]
     deny_withdrawal_dispute: bool,
+    /// Client id allowed to issue operator commands (`unlock`, `freeze`, `close`, `fee`). Can be
+    /// repeated.
+    #[clap(long = "operator", multiple_occurrences(true), number_of_values = 1)]
+    operators: Vec<ClientId>,
+    /// Reject disputes against transactions older than this number of days (default: no limit).
+    ///
+    /// Requires the `timestamp` column.
+    #[clap(long)]
+    dispute_window_days: Option<u64>,
+    /// Resolve disputes automatically once they are older than this number of days (default:
+    /// never).
+    ///
+    /// Requires the `timestamp` column.
+    #[clap(long)]
+    resolution_deadline_days: Option<u64>,
+    /// Flat fee charged for a command type, as `<type>=<amount>` (e.g. `withdrawal=0.5`). Can be
+    /// repeated for the types `deposit`, `withdrawal`, `transfer` and `chargeback`.
+    #[clap(long = "fee", multiple_occurrences(true), number_of_values = 1)]
+    fees: Vec<FeeArg<UnsignedAssetCount>>,
+    /// Percentage fee charged for a command type, as `<type>=<rate>` (e.g. `withdrawal=0.01`
+    /// for 1%). Can be repeated. Fees are rounded half to even.
+    #[clap(long = "fee-rate", multiple_occurrences(true), number_of_values = 1)]
+    fee_rates: Vec<FeeArg<FeeRate>>,
+    /// Accept commands when the account can't pay the fee, and record the missing part as a fee
+    /// debt (default: reject the command).
+    #[clap(long)]
+    fee_debt: bool,
+    /// Credit limit of a client account, as `<client>=<amount>` (e.g. `1=100.0`): its available
+    /// assets may go down to `-amount`. Can be repeated (default: no overdraft).
+    #[clap(
+        long = "credit-limit",
+        multiple_occurrences(true),
+        number_of_values = 1
+    )]
+    credit_limits: Vec<CreditLimitArg>,
+}
+
+/// A credit limit for a client, formatted as `<client>=<amount>`.
+#[derive(Debug)]
+struct CreditLimitArg {
+    client: ClientId,
+    limit: UnsignedAssetCount,
+}
+
+impl FromStr for CreditLimitArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (client, limit) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `<client>=<amount>`, got {:?}", s))?;
+        let client = client.trim().parse().map_err(|e| format!("{}", e))?;
+        let limit = limit
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid credit limit {:?}", limit))?;
+        Ok(Self { client, limit })
+    }
+}
+
+/// A fee for a command type, formatted as `<type>=<value>`.
+#[derive(Debug)]
+struct FeeArg<T> {
+    kind: FeeKind,
+    value: T,
+}
+
+impl<T: FromStr> FromStr for FeeArg<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `<type>=<value>`, got {:?}", s))?;
+        let kind = kind.trim().parse().map_err(|e| format!("{}", e))?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid fee value {:?}", value))?;
+        Ok(Self { kind, value })
+    }
 }
 
+/// Number of seconds in a day.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
 /// This is the main entry point of the program
 ///
 /// All the environment (except for the file system) is abstracted away so you
@@ -78,13 +177,53 @@ where
         }
     };
     let sort = args.sort;
-    let withdrawal_dispute_policy = if args.deny_withdrawal_dispute {
-        WithdrawalDisputePolicy::Deny
+    let columns = AccountColumns {
+        asset: args.asset_column,
+        status: args.status_columns,
+        fees: args.fee_columns,
+    };
+    let dispute_policy: Box<dyn DisputePolicy> = if args.deny_withdrawal_dispute {
+        Box::new(DenyWithdrawalDisputes)
     } else {
-        WithdrawalDisputePolicy::IfMoreAvailableThanDisputed
+        Box::new(IfMoreAvailableThanDisputed)
     };
+    let mut account_service = MemAccountService::new(dispute_policy);
+    for operator in args.operators {
+        account_service.add_operator(operator);
+    }
+    account_service.set_dispute_window(
+        args.dispute_window_days
+            .map(|days| Duration::from_secs(days.saturating_mul(SECS_PER_DAY))),
+    );
+    account_service.set_resolution_deadline(
+        args.resolution_deadline_days
+            .map(|days| Duration::from_secs(days.saturating_mul(SECS_PER_DAY))),
+    );
+    let mut fee_schedule = FeeSchedule::default();
+    for fee in args.fees {
+        fee_schedule.fee_mut(fee.kind).flat = fee.value;
+    }
+    for fee in args.fee_rates {
+        fee_schedule.fee_mut(fee.kind).rate = fee.value;
+    }
+    if args.fee_debt {
+        fee_schedule.insufficient_funds = InsufficientFeeFundsPolicy::Debt;
+    }
+    account_service.set_fee_schedule(fee_schedule);
+    for credit_limit in args.credit_limits {
+        if let Err(e) = account_service.set_credit_limit(credit_limit.client, credit_limit.limit) {
+            writeln!(
+                &mut stderr,
+                "Invalid credit limit for client #{}",
+                credit_limit.client
+            )
+            .expect("failed to write to stderr");
+            print_error_chain(&e, &mut stderr);
+            return exitcode::USAGE;
+        }
+    }
     let res = match args.input.as_deref() {
-        None => with_io(sort, withdrawal_dispute_policy, stdin, stdout, &mut stderr),
+        None => with_io(sort, columns, account_service, stdin, stdout, &mut stderr),
         Some(file) => {
             let file = match File::open(file) {
                 Ok(file) => file,
@@ -95,7 +234,7 @@ where
                     return exitcode::NOINPUT;
                 }
             };
-            with_io(sort, withdrawal_dispute_policy, file, stdout, &mut stderr)
+            with_io(sort, columns, account_service, file, stdout, &mut stderr)
         }
     };
 
@@ -111,14 +250,14 @@ where
     // Called once the args are validated and I/O is initialized
     fn with_io<Input: io::Read, Output: io::Write, ErrOutput: io::Write>(
         sort: bool,
-        withdrawal_dispute_policy: WithdrawalDisputePolicy,
+        mut columns: AccountColumns,
+        mut account_service: MemAccountService,
         input: Input,
         output: Output,
         mut err_output: ErrOutput,
     ) -> Result<(), Box<dyn std::error::Error + 'static>> {
         let mut csv_reader = CsvCommandReader::from_reader(input);
         let mut csv_writer = CsvAccountWriter::from_writer(output);
-        let mut account_service = MemAccountService::new(withdrawal_dispute_policy);
         // Main loop: Read from the CSV file and submit the commands
         for row in csv_reader.commands() {
             let cmd = match row.record {
@@ -136,6 +275,14 @@ where
             };
         }
         // Report the final state
+        columns.asset = columns.asset
+            || account_service.get_all_accounts().any(|account| {
+                account
+                    .balances
+                    .keys()
+                    .any(|asset| *asset != AssetId::DEFAULT)
+            });
+        csv_writer.set_columns(columns);
         let accounts = account_service.get_all_accounts();
         csv_writer.write_headers()?;
         if sort {
@@ -184,14 +331,34 @@ mod test {
     use crate::cli::run;
     use std::fs;
     use std::fs::File;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use test_generator::test_resources;
 
+    /// Cargo features changing the output of some test cases.
+    const OUTPUT_FEATURES: [(&str, bool); 2] = [
+        ("u128-asset-count", cfg!(feature = "u128-asset-count")),
+        ("string-client-id", cfg!(feature = "string-client-id")),
+    ];
+
+    /// Get the path of the expected output.
+    ///
+    /// Test cases depending on a feature (e.g. overflows) may provide an
+    /// `expected.<feature>.csv` file, used when the feature is enabled.
+    fn expected_path(test_item_dir: &Path) -> PathBuf {
+        for (feature, enabled) in OUTPUT_FEATURES {
+            let path = test_item_dir.join(format!("expected.{}.csv", feature));
+            if enabled && path.exists() {
+                return path;
+            }
+        }
+        test_item_dir.join("expected.csv")
+    }
+
     #[test_resources("./test-resources/*/")]
     fn test_app(path: &str) {
         let test_item_dir = PathBuf::from(path);
         let input_path = test_item_dir.join("input.csv");
-        let expected_path = test_item_dir.join("expected.csv");
+        let expected_path = expected_path(&test_item_dir);
         let actual_path = test_item_dir.join("actual.csv");
         let errors_path = test_item_dir.join("errors.log");
         let flags_path = test_item_dir.join("flags.txt");
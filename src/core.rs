@@ -1,10 +1,27 @@
-use crate::fixed_decimal::FixedDecimal;
-use serde::{Deserialize, Serialize};
+use crate::fixed_decimal::{
+    ArithmeticError, CheckedSum, FixedDecimal, FixedDecimalFormat, FormattedDecimal,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
 
-/// Clients are only referenced through their id, a valid `u16`.
+/// Integer type backing [ClientId].
+#[cfg(not(feature = "string-client-id"))]
+pub type ClientIdRepr = u64;
+
+/// Opaque string type backing [ClientId], with the `string-client-id` feature.
+#[cfg(feature = "string-client-id")]
+pub type ClientIdRepr = StringId;
+
+/// Clients are only referenced through their id.
+///
+/// By default, the id is a valid `u64`. With the `string-client-id` feature, the
+/// id is an opaque non-empty string of up to [STRING_ID_CAPACITY] bytes (e.g. a
+/// UUID); numeric ids are then treated as strings.
 ///
 /// Extended client profiles (name, address, ...) are not the responsibility of
 /// this service.
@@ -12,15 +29,271 @@ use thiserror::Error;
 /// This also serves as the account id in this lib (we assume there is a 1-1 mapping between
 /// accounts and clients).
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
-pub struct ClientId(u16);
+pub struct ClientId(ClientIdRepr);
 
 impl ClientId {
+    /// Create a client id from its numeric value.
+    ///
+    /// With the `string-client-id` feature, the id is the decimal representation of `id`.
+    pub const fn new(id: u64) -> Self {
+        #[cfg(not(feature = "string-client-id"))]
+        {
+            Self(id)
+        }
+        #[cfg(feature = "string-client-id")]
+        {
+            Self(StringId::from_u64(id))
+        }
+    }
+
+    /// Get the numeric value of this id.
+    ///
+    /// # Panics
+    ///
+    /// With the `string-client-id` feature, panics if the id is not the decimal
+    /// representation of a `u64`: see [ClientId::try_get] and [ClientId::string_id].
+    pub const fn get(self) -> u64 {
+        match self.try_get() {
+            Some(id) => id,
+            None => panic!("the client id is not numeric"),
+        }
+    }
+
+    /// Get the numeric value of this id, if any.
+    ///
+    /// Always returns `Some` without the `string-client-id` feature.
+    pub const fn try_get(self) -> Option<u64> {
+        #[cfg(not(feature = "string-client-id"))]
+        {
+            Some(self.0)
+        }
+        #[cfg(feature = "string-client-id")]
+        {
+            self.0.to_u64()
+        }
+    }
+
+    /// Get the string form of this id.
+    ///
+    /// Without the `string-client-id` feature, this is the decimal representation
+    /// of the numeric id.
+    pub const fn string_id(self) -> StringId {
+        #[cfg(not(feature = "string-client-id"))]
+        {
+            StringId::from_u64(self.0)
+        }
+        #[cfg(feature = "string-client-id")]
+        {
+            self.0
+        }
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for ClientId {
+    type Err = ParseClientIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(not(feature = "string-client-id"))]
+        {
+            s.parse().map(Self).map_err(|_| ParseClientIdError::Invalid)
+        }
+        #[cfg(feature = "string-client-id")]
+        {
+            s.parse().map(Self)
+        }
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParseClientIdError {
+    #[error("invalid numeric client id")]
+    Invalid,
+    #[error("empty client id")]
+    Empty,
+    #[error("client id is too long: {} bytes, max: {}", .0, STRING_ID_CAPACITY)]
+    TooLong(usize),
+}
+
+/// Maximum length of a [StringId], in bytes.
+pub const STRING_ID_CAPACITY: usize = 48;
+
+/// An opaque non-empty string id, stored inline so it can be copied.
+///
+/// Ids are compared by their string value.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct StringId {
+    len: u8,
+    /// UTF-8 bytes of the id, followed by zeros.
+    bytes: [u8; STRING_ID_CAPACITY],
+}
+
+impl StringId {
+    /// Create a string id from the decimal representation of `value`.
+    pub const fn from_u64(mut value: u64) -> Self {
+        let mut digits = [0u8; 20];
+        let mut len = 0;
+        loop {
+            digits[len] = b'0' + (value % 10) as u8;
+            len += 1;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        let mut bytes = [0u8; STRING_ID_CAPACITY];
+        let mut i = 0;
+        while i < len {
+            bytes[i] = digits[len - 1 - i];
+            i += 1;
+        }
+        Self {
+            len: len as u8,
+            bytes,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..usize::from(self.len)])
+            .expect("string id should always be valid UTF-8")
+    }
+
+    /// Parse the id as the decimal representation of a `u64`, as built by
+    /// [StringId::from_u64].
+    ///
+    /// Returns `None` for any other id, including ids with leading zeros.
+    pub const fn to_u64(&self) -> Option<u64> {
+        let len = self.len as usize;
+        if len > 1 && self.bytes[0] == b'0' {
+            return None;
+        }
+        let mut value: u64 = 0;
+        let mut i = 0;
+        while i < len {
+            let byte = self.bytes[i];
+            if !byte.is_ascii_digit() {
+                return None;
+            }
+            value = match value.checked_mul(10) {
+                Some(value) => value,
+                None => return None,
+            };
+            value = match value.checked_add((byte - b'0') as u64) {
+                Some(value) => value,
+                None => return None,
+            };
+            i += 1;
+        }
+        Some(value)
+    }
+}
+
+impl FromStr for StringId {
+    type Err = ParseClientIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseClientIdError::Empty);
+        }
+        if s.len() > STRING_ID_CAPACITY {
+            return Err(ParseClientIdError::TooLong(s.len()));
+        }
+        let mut bytes = [0u8; STRING_ID_CAPACITY];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Self {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl Ord for StringId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for StringId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Debug for StringId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for StringId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for StringId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for StringId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StringIdVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for StringIdVisitor {
+            type Value = StringId;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a non-empty string id with up to {} bytes",
+                    STRING_ID_CAPACITY
+                )
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(StringId::from_u64(value))
+            }
+        }
+
+        deserializer.deserialize_str(StringIdVisitor)
+    }
+}
+
+/// Assets (currencies) are only referenced through their id, a valid `u16`.
+///
+/// The meaning of the ids is defined by the caller service, for example the
+/// ISO 4217 numeric code of a currency. Commands which don't specify an asset
+/// use [AssetId::DEFAULT].
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Deserialize, Serialize,
+)]
+pub struct AssetId(u16);
+
+impl AssetId {
+    /// Asset used when none is specified.
+    pub const DEFAULT: Self = Self(0);
+
     pub const fn new(id: u16) -> Self {
         Self(id)
     }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
 }
 
-impl fmt::Display for ClientId {
+impl fmt::Display for AssetId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
     }
@@ -38,6 +311,10 @@ impl TransactionId {
     pub const fn new(id: u32) -> Self {
         Self(id)
     }
+
+    pub const fn get(self) -> u32 {
+        self.0
+    }
 }
 
 impl fmt::Display for TransactionId {
@@ -46,22 +323,77 @@ impl fmt::Display for TransactionId {
     }
 }
 
+/// Point in time when a command was issued, in seconds since the Unix epoch.
+///
+/// Timestamps are provided by the caller service: this lib never reads the
+/// system clock.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub const fn new(secs: u64) -> Self {
+        Self(secs)
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Get the timestamp `duration` after this one (rounded down to the second),
+    /// or `None` on overflow.
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(duration.as_secs()).map(Self)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Integer type backing [UnsignedAssetCount].
+///
+/// This is `u64` by default, or `u128` with the `u128-asset-count` feature.
+#[cfg(not(feature = "u128-asset-count"))]
+pub type AssetCountRepr = u64;
+
+/// Integer type backing [UnsignedAssetCount].
+///
+/// This is `u64` by default, or `u128` with the `u128-asset-count` feature.
+#[cfg(feature = "u128-asset-count")]
+pub type AssetCountRepr = u128;
+
 /// An unsigned amount of assets.
 ///
-/// This type is backed by a [FixedDecimal<u64, 4>]:
+/// This type is backed by a [FixedDecimal<AssetCountRepr, 4>]. By default, it is a `u64`:
 /// - Minimum value: `0.0000`
 /// - Maximum value: `1844674407370955.1615` (≃1.8e15)
 /// - Precision: `0.0001`
+///
+/// With the `u128-asset-count` feature, the maximum value is
+/// `34028236692093846346337460743176821.1455` (≃3.4e34).
 #[derive(
     Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Deserialize, Serialize,
 )]
-pub struct UnsignedAssetCount(FixedDecimal<u64, 4>);
+pub struct UnsignedAssetCount(FixedDecimal<AssetCountRepr, 4>);
 
 impl UnsignedAssetCount {
-    pub fn new(x: FixedDecimal<u64, 4>) -> Self {
+    pub fn new(x: FixedDecimal<AssetCountRepr, 4>) -> Self {
         Self(x)
     }
 
+    pub fn get(self) -> FixedDecimal<AssetCountRepr, 4> {
+        self.0
+    }
+
+    /// Parse an asset count literal in a `const` context.
+    ///
+    /// See [FixedDecimal::from_literal] and [crate::fixed!].
+    pub const fn from_literal(literal: &str) -> Self {
+        Self(FixedDecimal::<AssetCountRepr, 4>::from_literal(literal))
+    }
+
     pub fn checked_add(self, v: Self) -> Option<Self> {
         self.0.checked_add(&v.0).map(Self)
     }
@@ -69,6 +401,87 @@ impl UnsignedAssetCount {
     pub fn checked_sub(self, v: Self) -> Option<Self> {
         self.0.checked_sub(&v.0).map(Self)
     }
+
+    /// Format this amount with custom options, e.g. for human-readable reports.
+    ///
+    /// See [FixedDecimal::format_with].
+    pub fn format_with(
+        &self,
+        format: &FixedDecimalFormat,
+    ) -> Result<FormattedDecimal, ArithmeticError> {
+        self.0.format_with(format)
+    }
+
+    /// Split this amount in parts proportional to the provided weights, without losing any
+    /// fraction.
+    ///
+    /// See [FixedDecimal::allocate].
+    pub fn allocate(self, weights: &[AssetCountRepr]) -> Option<Vec<Self>> {
+        self.0
+            .allocate(weights)
+            .map(|parts| parts.into_iter().map(Self).collect())
+    }
+
+    /// Split this amount in parts proportional to the provided decimal weights, without
+    /// losing any fraction.
+    ///
+    /// See [FixedDecimal::allocate_by].
+    pub fn allocate_by<const WEIGHT_PRECISION: u8>(
+        self,
+        weights: &[FixedDecimal<AssetCountRepr, WEIGHT_PRECISION>],
+    ) -> Option<Vec<Self>> {
+        self.0
+            .allocate_by(weights)
+            .map(|parts| parts.into_iter().map(Self).collect())
+    }
+
+    /// Split this amount in `n` parts as equal as possible, without losing any fraction.
+    ///
+    /// See [FixedDecimal::split].
+    pub fn split(self, n: usize) -> Option<Vec<Self>> {
+        self.0
+            .split(n)
+            .map(|parts| parts.into_iter().map(Self).collect())
+    }
+
+    /// Deserialize from a string or an exact integer.
+    ///
+    /// See [FixedDecimal::deserialize_number].
+    pub fn deserialize_number<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        FixedDecimal::deserialize_number(deserializer).map(Self)
+    }
+
+    /// Deserialize from a string, an exact integer or an exact float.
+    ///
+    /// See [FixedDecimal::deserialize_number_or_float].
+    pub fn deserialize_number_or_float<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        FixedDecimal::deserialize_number_or_float(deserializer).map(Self)
+    }
+}
+
+impl CheckedSum for UnsignedAssetCount {
+    fn checked_sum<I: Iterator<Item = Self>>(iter: I) -> Option<Self> {
+        FixedDecimal::checked_sum(iter.map(|v| v.0)).map(Self)
+    }
+}
+
+impl<'a> CheckedSum<&'a Self> for UnsignedAssetCount {
+    fn checked_sum<I: Iterator<Item = &'a Self>>(iter: I) -> Option<Self> {
+        FixedDecimal::checked_sum(iter.map(|v| v.0)).map(Self)
+    }
+}
+
+/// Sum unsigned asset counts into a wider signed total.
+impl CheckedSum<UnsignedAssetCount> for SignedAssetCount {
+    fn checked_sum<I: Iterator<Item = UnsignedAssetCount>>(mut iter: I) -> Option<Self> {
+        iter.try_fold(Self::default(), |acc, v| {
+            acc.checked_add(Self::try_from(v).ok()?)
+        })
+    }
 }
 
 impl fmt::Display for UnsignedAssetCount {
@@ -85,6 +498,122 @@ impl FromStr for UnsignedAssetCount {
     }
 }
 
+/// A signed amount of assets, used to represent deltas such as corrections or net flows.
+///
+/// This type is backed by a [FixedDecimal<i128, 4>], so any [UnsignedAssetCount] with the
+/// default `u64` backing can be represented with either sign:
+/// - Minimum value: `-17014118346046923173168730371588410.5728` (≃-1.7e34)
+/// - Maximum value: `17014118346046923173168730371588410.5727` (≃1.7e34)
+/// - Precision: `0.0001`
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Deserialize, Serialize,
+)]
+pub struct SignedAssetCount(FixedDecimal<i128, 4>);
+
+/// Error when converting between [SignedAssetCount] and [UnsignedAssetCount].
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("asset count out of range for the target type")]
+pub struct AssetCountRangeError;
+
+impl SignedAssetCount {
+    pub fn new(x: FixedDecimal<i128, 4>) -> Self {
+        Self(x)
+    }
+
+    /// Parse an asset count literal in a `const` context.
+    ///
+    /// See [FixedDecimal::from_literal] and [crate::fixed!].
+    pub const fn from_literal(literal: &str) -> Self {
+        Self(FixedDecimal::<i128, 4>::from_literal(literal))
+    }
+
+    pub fn checked_add(self, v: Self) -> Option<Self> {
+        self.0.checked_add(&v.0).map(Self)
+    }
+
+    pub fn checked_sub(self, v: Self) -> Option<Self> {
+        self.0.checked_sub(&v.0).map(Self)
+    }
+
+    /// Format this amount with custom options, e.g. for human-readable reports.
+    ///
+    /// See [FixedDecimal::format_with].
+    pub fn format_with(
+        &self,
+        format: &FixedDecimalFormat,
+    ) -> Result<FormattedDecimal, ArithmeticError> {
+        self.0.format_with(format)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
+
+    /// Get the absolute value, as an unsigned asset count.
+    ///
+    /// Returns `None` if the magnitude exceeds the range of [UnsignedAssetCount].
+    pub fn abs(self) -> Option<UnsignedAssetCount> {
+        self.0
+            .checked_abs()
+            .and_then(|abs| UnsignedAssetCount::try_from(Self(abs)).ok())
+    }
+
+    pub fn is_negative(self) -> bool {
+        *self.0.fractions() < 0
+    }
+
+    /// Deserialize from a string or an exact integer.
+    ///
+    /// See [FixedDecimal::deserialize_number].
+    pub fn deserialize_number<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        FixedDecimal::deserialize_number(deserializer).map(Self)
+    }
+}
+
+impl CheckedSum for SignedAssetCount {
+    fn checked_sum<I: Iterator<Item = Self>>(iter: I) -> Option<Self> {
+        FixedDecimal::checked_sum(iter.map(|v| v.0)).map(Self)
+    }
+}
+
+impl<'a> CheckedSum<&'a Self> for SignedAssetCount {
+    fn checked_sum<I: Iterator<Item = &'a Self>>(iter: I) -> Option<Self> {
+        FixedDecimal::checked_sum(iter.map(|v| v.0)).map(Self)
+    }
+}
+
+impl TryFrom<UnsignedAssetCount> for SignedAssetCount {
+    type Error = AssetCountRangeError;
+
+    fn try_from(value: UnsignedAssetCount) -> Result<Self, Self::Error> {
+        value.0.checked_cast().map(Self).ok_or(AssetCountRangeError)
+    }
+}
+
+impl TryFrom<SignedAssetCount> for UnsignedAssetCount {
+    type Error = AssetCountRangeError;
+
+    fn try_from(value: SignedAssetCount) -> Result<Self, Self::Error> {
+        value.0.checked_cast().map(Self).ok_or(AssetCountRangeError)
+    }
+}
+
+impl fmt::Display for SignedAssetCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for SignedAssetCount {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.parse().map_err(drop)?))
+    }
+}
+
 /// Metadata common to both `Deposit` and `Withdrawal` transactions.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct TransactionMeta {
@@ -92,8 +621,12 @@ pub struct TransactionMeta {
     pub id: TransactionId,
     /// Client who initiated the transaction
     pub client: ClientId,
+    /// Asset moved by this transaction
+    pub asset: AssetId,
     /// Asset amount to move for this transaction
     pub amount: UnsignedAssetCount,
+    /// Time when the transaction was issued, if known
+    pub timestamp: Option<Timestamp>,
 }
 
 impl TransactionMeta {
@@ -104,6 +637,11 @@ impl TransactionMeta {
     pub fn to_withdrawal_tx(self) -> Transaction {
         Transaction::Withdrawal(self)
     }
+
+    /// Create a transfer from `self.client` to `to`.
+    pub fn to_transfer_tx(self, to: ClientId) -> Transaction {
+        Transaction::Transfer { meta: self, to }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -112,6 +650,13 @@ pub enum Transaction {
     Deposit(TransactionMeta),
     /// Remove an amount of currency from a non-frozen account.
     Withdrawal(TransactionMeta),
+    /// Move an amount of currency from the non-frozen account `meta.client` to
+    /// the non-frozen account `to`.
+    Transfer {
+        meta: TransactionMeta,
+        /// Client receiving the assets
+        to: ClientId,
+    },
 }
 
 impl Transaction {
@@ -125,31 +670,141 @@ impl Transaction {
         self.meta().client
     }
 
+    /// Get the client id of the account holding the assets affected by disputes
+    ///
+    /// This is the account owner for deposits and withdrawals, and the
+    /// recipient for transfers.
+    pub const fn holder(&self) -> ClientId {
+        match self {
+            Self::Deposit(ref tx) => tx.client,
+            Self::Withdrawal(ref tx) => tx.client,
+            Self::Transfer { to, .. } => *to,
+        }
+    }
+
+    /// Get the asset moved by the transaction, regardless of the transaction type
+    pub const fn asset(&self) -> AssetId {
+        self.meta().asset
+    }
+
     /// Get the transaction amount, regardless of the transaction type
     pub const fn amount(&self) -> UnsignedAssetCount {
         self.meta().amount
     }
 
+    /// Get the time when the transaction was issued, regardless of the transaction type
+    pub const fn timestamp(&self) -> Option<Timestamp> {
+        self.meta().timestamp
+    }
+
+    /// Check if `other` has the same fields as this transaction, ignoring the timestamps
+    ///
+    /// The timestamp records when a command was issued, so retrying the same
+    /// command may change or drop it.
+    pub fn is_retry_of(&self, other: &Self) -> bool {
+        self.without_timestamp() == other.without_timestamp()
+    }
+
+    fn without_timestamp(mut self) -> Self {
+        match self {
+            Self::Deposit(ref mut meta)
+            | Self::Withdrawal(ref mut meta)
+            | Self::Transfer { ref mut meta, .. } => meta.timestamp = None,
+        }
+        self
+    }
+
     const fn meta(&self) -> &TransactionMeta {
         match self {
             Self::Deposit(ref tx) => tx,
             Self::Withdrawal(ref tx) => tx,
+            Self::Transfer { ref meta, .. } => meta,
         }
     }
 }
 
 /// Represents an account state, as returned in the output.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Account {
     /// Reference to the client owning this account
     pub client: ClientId,
-    /// Current balance of the account
-    pub balance: AccountBalance,
-    /// Locked state of the account.
+    /// Current balance of the account, for each asset
+    ///
+    /// Assets never used by the account are omitted.
+    pub balances: BTreeMap<AssetId, AccountBalance>,
+    /// Status of the account, see [AccountStatus].
+    pub status: AccountStatus,
+}
+
+impl Account {
+    /// Get the balance for the provided asset (empty if the asset was never used)
+    pub fn balance(&self, asset: AssetId) -> AccountBalance {
+        self.balances.get(&asset).copied().unwrap_or_default()
+    }
+
+    /// Check if the account is locked, regardless of the reason.
+    pub fn is_locked(&self) -> bool {
+        self.status.is_locked()
+    }
+}
+
+/// Status of an account.
+///
+/// If an account is not `Active`, it should not be possible to update any of
+/// its balances.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum AccountStatus {
+    /// Regular account, all the commands are allowed.
+    Active,
+    /// The account was locked automatically following a transaction.
     ///
-    /// If an account is locked, it should not be possible to update its
-    /// balance.
-    pub locked: bool,
+    /// An operator can unlock the account once the situation is investigated.
+    Locked {
+        reason: LockReason,
+        /// Transaction which caused the lock.
+        by_tx: TransactionId,
+    },
+    /// The account was locked by an operator.
+    Frozen,
+    /// The account is permanently closed, it can't be unlocked.
+    Closed,
+}
+
+impl Default for AccountStatus {
+    fn default() -> Self {
+        Self::Active
+    }
+}
+
+impl AccountStatus {
+    pub const fn is_locked(self) -> bool {
+        !matches!(self, Self::Active)
+    }
+
+    /// Name of the status, as used in the output.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Locked { .. } => "locked",
+            Self::Frozen => "frozen",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+/// Reason why an account was locked automatically.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum LockReason {
+    /// The transaction was disputed and charged back.
+    Chargeback,
+}
+
+impl fmt::Display for LockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chargeback => f.write_str("chargeback"),
+        }
+    }
 }
 
 /// Current balance of an account
@@ -161,13 +816,29 @@ pub struct Account {
 /// The balance also allows to retrieve the total amount associated with the
 /// account. The total is always the sum of the available and held amounts.
 ///
-/// This struct enforces that both the `available` and `held` assets are
-/// always positive. It also prevents any updated that would cause an
-/// overflow or underflow of the `available`, `held` or `total` values.
+/// The available amount may be negative, down to `-credit_limit`. The credit
+/// limit is zero by default, so the available amount is never negative unless
+/// an overdraft is explicitly allowed.
+///
+/// Fees are tracked separately: `fees` is the amount of fees paid so far and
+/// `fee_debt` the amount of fees charged without sufficient available assets.
+/// They are not part of the total.
+///
+/// This struct enforces that the `held` assets are always positive and that
+/// the `available` assets never go below the credit limit. It also prevents
+/// any updated that would cause an overflow or underflow of the `available`,
+/// `held` or `total` values: they must fit in an [UnsignedAssetCount] when
+/// positive.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct AccountBalance {
-    available: UnsignedAssetCount,
+    available: SignedAssetCount,
     held: UnsignedAssetCount,
+    #[serde(default)]
+    credit_limit: UnsignedAssetCount,
+    #[serde(default)]
+    fees: UnsignedAssetCount,
+    #[serde(default)]
+    fee_debt: UnsignedAssetCount,
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -178,8 +849,11 @@ impl AccountBalance {
     /// Create a new empty account balance.
     pub fn new() -> Self {
         Self {
-            available: UnsignedAssetCount::default(),
+            available: SignedAssetCount::default(),
             held: UnsignedAssetCount::default(),
+            credit_limit: UnsignedAssetCount::default(),
+            fees: UnsignedAssetCount::default(),
+            fee_debt: UnsignedAssetCount::default(),
         }
     }
 
@@ -189,12 +863,14 @@ impl AccountBalance {
         held: UnsignedAssetCount,
     ) -> Result<Self, BalanceUpdateError> {
         let mut balance = Self::new();
-        balance.update(available, held)?;
+        balance.update(to_signed(available)?, held)?;
         Ok(balance)
     }
 
     /// Get the current available (non-disputed) amount of currency
-    pub fn available(self) -> UnsignedAssetCount {
+    ///
+    /// It is negative if the account uses its credit limit.
+    pub fn available(self) -> SignedAssetCount {
         self.available
     }
 
@@ -204,12 +880,118 @@ impl AccountBalance {
     }
 
     /// Get the total amount of currency
-    pub fn total(self) -> UnsignedAssetCount {
-        self.available
-            .checked_add(self.held)
+    pub fn total(self) -> SignedAssetCount {
+        to_signed(self.held)
+            .ok()
+            .and_then(|held| self.available.checked_add(held))
             .expect("internal invariant should enforce that computing the total always succeeds")
     }
 
+    /// Get the maximum overdraft: `available` may go down to `-credit_limit`
+    pub fn credit_limit(self) -> UnsignedAssetCount {
+        self.credit_limit
+    }
+
+    /// Change the credit limit.
+    ///
+    /// Errors if the current `available` value is below the new limit.
+    ///
+    /// This update is atomic.
+    pub fn set_credit_limit(
+        &mut self,
+        limit: UnsignedAssetCount,
+    ) -> Result<(), BalanceUpdateError> {
+        let mut new_balance = *self;
+        new_balance.credit_limit = limit;
+        new_balance.update(self.available, self.held)?;
+        *self = new_balance;
+        Ok(())
+    }
+
+    /// Check if `amount` can be removed from the `available` assets without
+    /// exceeding the credit limit.
+    pub fn can_spend(self, amount: UnsignedAssetCount) -> bool {
+        self.spendable()
+            .map_or(false, |spendable| spendable >= amount)
+    }
+
+    /// Get the amount which can be removed from the `available` assets without
+    /// exceeding the credit limit, if it fits in an [UnsignedAssetCount].
+    fn spendable(self) -> Option<UnsignedAssetCount> {
+        let limit = to_signed(self.credit_limit).ok()?;
+        let spendable = self.available.checked_add(limit)?;
+        UnsignedAssetCount::try_from(spendable).ok()
+    }
+
+    /// Get the amount of fees paid so far
+    pub fn fees(self) -> UnsignedAssetCount {
+        self.fees
+    }
+
+    /// Get the amount of fees charged but not paid yet
+    pub fn fee_debt(self) -> UnsignedAssetCount {
+        self.fee_debt
+    }
+
+    /// Check if the balance has no assets, no overdraft and no fee debt (paid
+    /// fees are ignored)
+    pub fn is_empty(self) -> bool {
+        let zero = UnsignedAssetCount::default();
+        self.available == SignedAssetCount::default() && self.held == zero && self.fee_debt == zero
+    }
+
+    /// Pay a fee from the `available` assets, within the credit limit.
+    ///
+    /// If the available assets are insufficient, the update fails unless
+    /// `allow_debt` is set: in this case, the non-negative part of the
+    /// available assets is used (the credit line is left untouched) and the
+    /// remaining part is added to the fee debt.
+    ///
+    /// Returns the part of the fee added to the debt.
+    ///
+    /// This update is atomic.
+    pub fn pay_fee(
+        &mut self,
+        amount: UnsignedAssetCount,
+        allow_debt: bool,
+    ) -> Result<UnsignedAssetCount, BalanceUpdateError> {
+        let paid = if self.can_spend(amount) {
+            amount
+        } else if allow_debt {
+            self.unsigned_available()
+        } else {
+            return Err(BalanceUpdateError);
+        };
+        let debt = amount.checked_sub(paid).ok_or(BalanceUpdateError)?;
+        let mut new_balance = *self;
+        new_balance.fees = self.fees.checked_add(paid).ok_or(BalanceUpdateError)?;
+        new_balance.fee_debt = self.fee_debt.checked_add(debt).ok_or(BalanceUpdateError)?;
+        new_balance.dec_available(paid)?;
+        *self = new_balance;
+        Ok(debt)
+    }
+
+    /// Pay as much of the fee debt as possible with the `available` assets.
+    ///
+    /// The debt is never paid with the credit line: `available` does not go
+    /// below zero.
+    ///
+    /// This update is atomic.
+    pub fn collect_fee_debt(&mut self) -> Result<(), BalanceUpdateError> {
+        let paid = self.fee_debt.min(self.unsigned_available());
+        let mut new_balance = *self;
+        new_balance.fees = self.fees.checked_add(paid).ok_or(BalanceUpdateError)?;
+        new_balance.fee_debt = self.fee_debt.checked_sub(paid).ok_or(BalanceUpdateError)?;
+        new_balance.dec_available(paid)?;
+        *self = new_balance;
+        Ok(())
+    }
+
+    /// Get the `available` assets, or zero if they are negative.
+    fn unsigned_available(self) -> UnsignedAssetCount {
+        UnsignedAssetCount::try_from(self.available).unwrap_or_default()
+    }
+
     /// Increment the `available` value by the provided amount
     ///
     /// Errors if the update causes an underflow/overflow
@@ -218,7 +1000,7 @@ impl AccountBalance {
     pub fn inc_available(&mut self, amount: UnsignedAssetCount) -> Result<(), BalanceUpdateError> {
         let new_available = self
             .available
-            .checked_add(amount)
+            .checked_add(to_signed(amount)?)
             .ok_or(BalanceUpdateError)?;
         self.update(new_available, self.held)
     }
@@ -236,7 +1018,7 @@ impl AccountBalance {
     ) -> Result<(), BalanceUpdateError> {
         let new_available = self
             .available
-            .checked_sub(amount)
+            .checked_sub(to_signed(amount)?)
             .ok_or(BalanceUpdateError)?;
         let new_held = self.held.checked_add(amount).ok_or(BalanceUpdateError)?;
         self.update(new_available, new_held)
@@ -255,7 +1037,7 @@ impl AccountBalance {
     ) -> Result<(), BalanceUpdateError> {
         let new_available = self
             .available
-            .checked_add(amount)
+            .checked_add(to_signed(amount)?)
             .ok_or(BalanceUpdateError)?;
         let new_held = self.held.checked_sub(amount).ok_or(BalanceUpdateError)?;
         self.update(new_available, new_held)
@@ -269,21 +1051,43 @@ impl AccountBalance {
     pub fn dec_available(&mut self, amount: UnsignedAssetCount) -> Result<(), BalanceUpdateError> {
         let new_available = self
             .available
-            .checked_sub(amount)
+            .checked_sub(to_signed(amount)?)
             .ok_or(BalanceUpdateError)?;
         self.update(new_available, self.held)
     }
 
+    /// Decrement the `held` value by the provided amount
+    ///
+    /// Errors if the update causes an underflow/overflow
+    ///
+    /// This update is atomic.
+    pub fn dec_held(&mut self, amount: UnsignedAssetCount) -> Result<(), BalanceUpdateError> {
+        let new_held = self.held.checked_sub(amount).ok_or(BalanceUpdateError)?;
+        self.update(self.available, new_held)
+    }
+
     /// Perform an atomic update of the account balance.
     ///
-    /// The update fails if it causes any overflow or underflow.
+    /// The update fails if it causes any overflow or underflow, or if
+    /// `new_available` is below the credit limit.
     pub fn update(
         &mut self,
-        new_available: UnsignedAssetCount,
+        new_available: SignedAssetCount,
         new_held: UnsignedAssetCount,
     ) -> Result<(), BalanceUpdateError> {
-        let total_sum_is_safe_to_compute = new_available.checked_add(new_held).is_some();
-        if !total_sum_is_safe_to_compute {
+        let min_available = to_signed(self.credit_limit)?
+            .checked_neg()
+            .ok_or(BalanceUpdateError)?;
+        if new_available < min_available {
+            return Err(BalanceUpdateError);
+        }
+        let total = new_available
+            .checked_add(to_signed(new_held)?)
+            .ok_or(BalanceUpdateError)?;
+        let fits_unsigned = |value: SignedAssetCount| {
+            value.is_negative() || UnsignedAssetCount::try_from(value).is_ok()
+        };
+        if !fits_unsigned(new_available) || !fits_unsigned(total) {
             return Err(BalanceUpdateError);
         }
         self.available = new_available;
@@ -292,6 +1096,11 @@ impl AccountBalance {
     }
 }
 
+/// Convert an amount for balance updates.
+fn to_signed(amount: UnsignedAssetCount) -> Result<SignedAssetCount, BalanceUpdateError> {
+    SignedAssetCount::try_from(amount).map_err(|_| BalanceUpdateError)
+}
+
 impl Default for AccountBalance {
     fn default() -> Self {
         Self::new()
@@ -303,13 +1112,38 @@ impl Default for AccountBalance {
 pub enum Command {
     Deposit(cmd::Deposit),
     Withdrawal(cmd::Withdrawal),
+    Transfer(cmd::Transfer),
     Dispute(cmd::Dispute),
     Resolve(cmd::Resolve),
     Chargeback(cmd::Chargeback),
+    Unlock(cmd::Unlock),
+    Freeze(cmd::Freeze),
+    Close(cmd::Close),
+    Fee(cmd::Fee),
+}
+
+impl Command {
+    /// Get the time when the command was issued, regardless of the command type
+    pub const fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Self::Deposit(cmd) => cmd.0.timestamp,
+            Self::Withdrawal(cmd) => cmd.0.timestamp,
+            Self::Transfer(cmd) => cmd.meta.timestamp,
+            Self::Dispute(cmd) => cmd.timestamp,
+            Self::Resolve(cmd) => cmd.timestamp,
+            Self::Chargeback(cmd) => cmd.timestamp,
+            Self::Unlock(cmd) => cmd.timestamp,
+            Self::Freeze(cmd) => cmd.timestamp,
+            Self::Close(cmd) => cmd.timestamp,
+            Self::Fee(cmd) => cmd.timestamp,
+        }
+    }
 }
 
 pub mod cmd {
-    use crate::core::{ClientId, TransactionId, TransactionMeta};
+    use crate::core::{
+        AssetId, ClientId, Timestamp, TransactionId, TransactionMeta, UnsignedAssetCount,
+    };
 
     /// Increase the available assets of an account.
     #[derive(Debug, Clone, Eq, PartialEq)]
@@ -321,15 +1155,32 @@ pub mod cmd {
     #[derive(Debug, Clone, Eq, PartialEq)]
     pub struct Withdrawal(pub TransactionMeta);
 
+    /// Move assets from an account to another, atomically.
+    ///
+    /// Requires both accounts to be unlocked and the sender to have enough
+    /// available assets.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Transfer {
+        /// Transaction metadata, `meta.client` is the sender.
+        pub meta: TransactionMeta,
+        /// Client receiving the assets.
+        pub to: ClientId,
+    }
+
     /// Signal that a previous transaction was erroneous.
     ///
     /// Only the account owner can file a dispute. The assets involved will
-    /// be frozen in a `held` state until the dispute is settled.
+    /// be frozen in a `held` state until the dispute is settled. Only the
+    /// balance for the asset of the disputed transaction is affected.
     #[derive(Debug, Clone, Eq, PartialEq)]
     pub struct Dispute {
         /// Client claiming that a previous transaction was erroneous.
         pub client: ClientId,
         pub tx: TransactionId,
+        /// Amount disputed, defaults to the whole undisputed amount.
+        pub amount: Option<UnsignedAssetCount>,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
     }
 
     /// Settle a dispute as resolve (the transaction was in fact valid)
@@ -340,6 +1191,10 @@ pub mod cmd {
         /// Client settling the dispute as resolved.
         pub client: ClientId,
         pub tx: TransactionId,
+        /// Amount resolved, defaults to the whole disputed amount.
+        pub amount: Option<UnsignedAssetCount>,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
     }
 
     /// Settle a dispute with a chargeback (cancel the disputed transaction)
@@ -350,22 +1205,370 @@ pub mod cmd {
         /// Client settling the dispute with chargeback.
         pub client: ClientId,
         pub tx: TransactionId,
+        /// Amount charged back, defaults to the whole disputed amount.
+        pub amount: Option<UnsignedAssetCount>,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
+    }
+
+    /// Lift the lock of an account (e.g. once the investigation following a
+    /// chargeback is over).
+    ///
+    /// Only operators can unlock accounts. Closed accounts can't be unlocked.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Unlock {
+        /// Operator issuing the command.
+        pub operator: ClientId,
+        /// Account to unlock.
+        pub client: ClientId,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
+    }
+
+    /// Lock an account without any chargeback (e.g. to investigate suspicious
+    /// activity).
+    ///
+    /// Only operators can freeze accounts.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Freeze {
+        /// Operator issuing the command.
+        pub operator: ClientId,
+        /// Account to freeze.
+        pub client: ClientId,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
+    }
+
+    /// Permanently close an account.
+    ///
+    /// Only operators can close accounts, and all the balances of the account
+    /// must be empty. A closed account stays locked forever.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Close {
+        /// Operator issuing the command.
+        pub operator: ClientId,
+        /// Account to close.
+        pub client: ClientId,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
+    }
+
+    /// Charge a fee to an account, outside of the fee schedule.
+    ///
+    /// Only operators can charge fees.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Fee {
+        /// Operator issuing the command.
+        pub operator: ClientId,
+        /// Account paying the fee.
+        pub client: ClientId,
+        /// Asset used to pay the fee.
+        pub asset: AssetId,
+        /// Fee amount.
+        pub amount: UnsignedAssetCount,
+        /// Transaction the fee relates to, if any.
+        pub tx: Option<TransactionId>,
+        /// Time when the command was issued, if known
+        pub timestamp: Option<Timestamp>,
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{AccountBalance, UnsignedAssetCount};
-    use crate::fixed_decimal::FixedDecimal;
+    use super::{
+        AccountBalance, AssetId, BalanceUpdateError, ClientId, ParseClientIdError,
+        SignedAssetCount, StringId, Timestamp, TransactionId, TransactionMeta, UnsignedAssetCount,
+        STRING_ID_CAPACITY,
+    };
+    use std::convert::TryFrom;
+
+    #[test]
+    fn client_id_display_and_parse() {
+        let id = ClientId::new(u64::MAX);
+        assert_eq!(id.to_string(), "18446744073709551615");
+        assert_eq!("18446744073709551615".parse::<ClientId>(), Ok(id));
+    }
+
+    #[test]
+    #[cfg(not(feature = "string-client-id"))]
+    fn client_id_parse_invalid() {
+        assert_eq!(
+            "18446744073709551616".parse::<ClientId>(),
+            Err(ParseClientIdError::Invalid)
+        );
+    }
+
+    #[test]
+    fn string_id_from_u64() {
+        assert_eq!(StringId::from_u64(0).as_str(), "0");
+        assert_eq!(StringId::from_u64(1234).as_str(), "1234");
+        assert_eq!(StringId::from_u64(1234), "1234".parse().unwrap());
+        assert_eq!(
+            StringId::from_u64(u64::MAX).as_str(),
+            "18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn string_id_parse() {
+        let uuid = "4e9b8d7a-6a3c-4c1f-9a46-0b0c1a5c6a2e";
+        let id: StringId = uuid.parse().unwrap();
+        assert_eq!(id.as_str(), uuid);
+        assert_eq!(id.to_string(), uuid);
+        assert_eq!("".parse::<StringId>(), Err(ParseClientIdError::Empty));
+        let too_long = "a".repeat(STRING_ID_CAPACITY + 1);
+        assert_eq!(
+            too_long.parse::<StringId>(),
+            Err(ParseClientIdError::TooLong(STRING_ID_CAPACITY + 1))
+        );
+    }
+
+    #[test]
+    fn client_id_accessors() {
+        let id = ClientId::new(u64::MAX);
+        assert_eq!(id.get(), u64::MAX);
+        assert_eq!(id.try_get(), Some(u64::MAX));
+        assert_eq!(id.string_id().as_str(), "18446744073709551615");
+    }
+
+    #[test]
+    #[cfg(feature = "string-client-id")]
+    fn client_id_not_numeric() {
+        let id: ClientId = "4e9b8d7a".parse().unwrap();
+        assert_eq!(id.try_get(), None);
+        assert_eq!(id.string_id().as_str(), "4e9b8d7a");
+        let id: ClientId = "007".parse().unwrap();
+        assert_eq!(id.try_get(), None);
+    }
+
+    #[test]
+    fn string_id_to_u64() {
+        assert_eq!(StringId::from_u64(0).to_u64(), Some(0));
+        assert_eq!(StringId::from_u64(u64::MAX).to_u64(), Some(u64::MAX));
+        let parse = |s: &str| s.parse::<StringId>().unwrap().to_u64();
+        assert_eq!(parse("18446744073709551616"), None);
+        assert_eq!(parse("01"), None);
+        assert_eq!(parse("1a"), None);
+    }
+
+    #[test]
+    fn string_id_ordering() {
+        let a: StringId = "ab".parse().unwrap();
+        let b: StringId = "b".parse().unwrap();
+        assert!(a < b);
+    }
 
     #[test]
     fn default_balance_is_empty() {
         let actual = AccountBalance::default();
         let expected = AccountBalance::new_with(
-            UnsignedAssetCount::new(FixedDecimal::from_fractions(0)),
-            UnsignedAssetCount::new(FixedDecimal::from_fractions(0)),
+            crate::fixed!(UnsignedAssetCount, "0"),
+            crate::fixed!(UnsignedAssetCount, "0"),
         )
         .unwrap();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn pay_fee_without_debt() {
+        let mut balance = AccountBalance::new_with(
+            crate::fixed!(UnsignedAssetCount, "1"),
+            crate::fixed!(UnsignedAssetCount, "2"),
+        )
+        .unwrap();
+        assert_eq!(
+            balance.pay_fee(crate::fixed!(UnsignedAssetCount, "0.25"), false),
+            Ok(crate::fixed!(UnsignedAssetCount, "0"))
+        );
+        assert_eq!(
+            balance.pay_fee(crate::fixed!(UnsignedAssetCount, "1"), false),
+            Err(BalanceUpdateError)
+        );
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "0.75"));
+        assert_eq!(balance.held(), crate::fixed!(UnsignedAssetCount, "2"));
+        assert_eq!(balance.fees(), crate::fixed!(UnsignedAssetCount, "0.25"));
+        assert_eq!(balance.fee_debt(), crate::fixed!(UnsignedAssetCount, "0"));
+    }
+
+    #[test]
+    fn pay_fee_with_debt_then_collect() {
+        let mut balance = AccountBalance::new_with(
+            crate::fixed!(UnsignedAssetCount, "1"),
+            crate::fixed!(UnsignedAssetCount, "0"),
+        )
+        .unwrap();
+        assert_eq!(
+            balance.pay_fee(crate::fixed!(UnsignedAssetCount, "3"), true),
+            Ok(crate::fixed!(UnsignedAssetCount, "2"))
+        );
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "0"));
+        assert_eq!(balance.fees(), crate::fixed!(UnsignedAssetCount, "1"));
+        assert_eq!(balance.fee_debt(), crate::fixed!(UnsignedAssetCount, "2"));
+        assert!(!balance.is_empty());
+
+        balance
+            .inc_available(crate::fixed!(UnsignedAssetCount, "5"))
+            .unwrap();
+        balance.collect_fee_debt().unwrap();
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "3"));
+        assert_eq!(balance.fees(), crate::fixed!(UnsignedAssetCount, "3"));
+        assert_eq!(balance.fee_debt(), crate::fixed!(UnsignedAssetCount, "0"));
+    }
+
+    #[test]
+    fn fee_debt_does_not_use_credit() {
+        let mut balance = AccountBalance::new_with(
+            crate::fixed!(UnsignedAssetCount, "1"),
+            crate::fixed!(UnsignedAssetCount, "0"),
+        )
+        .unwrap();
+        balance
+            .set_credit_limit(crate::fixed!(UnsignedAssetCount, "10"))
+            .unwrap();
+        // Fees within the credit limit are paid in full
+        assert_eq!(
+            balance.pay_fee(crate::fixed!(UnsignedAssetCount, "3"), true),
+            Ok(crate::fixed!(UnsignedAssetCount, "0"))
+        );
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "-2"));
+        // Fees above the credit limit go to the debt without using more credit
+        assert_eq!(
+            balance.pay_fee(crate::fixed!(UnsignedAssetCount, "9"), true),
+            Ok(crate::fixed!(UnsignedAssetCount, "9"))
+        );
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "-2"));
+
+        balance
+            .inc_available(crate::fixed!(UnsignedAssetCount, "5"))
+            .unwrap();
+        balance.collect_fee_debt().unwrap();
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "0"));
+        assert_eq!(balance.fees(), crate::fixed!(UnsignedAssetCount, "6"));
+        assert_eq!(balance.fee_debt(), crate::fixed!(UnsignedAssetCount, "6"));
+    }
+
+    #[test]
+    fn transaction_retry_ignores_timestamp() {
+        let meta = TransactionMeta {
+            id: TransactionId::new(1),
+            client: ClientId::new(2),
+            asset: AssetId::DEFAULT,
+            amount: crate::fixed!(UnsignedAssetCount, "3"),
+            timestamp: Some(Timestamp::new(100)),
+        };
+        let tx = meta.to_deposit_tx();
+        let later = TransactionMeta {
+            timestamp: Some(Timestamp::new(200)),
+            ..meta
+        };
+        let missing = TransactionMeta {
+            timestamp: None,
+            ..meta
+        };
+        assert!(tx.is_retry_of(&later.to_deposit_tx()));
+        assert!(tx.is_retry_of(&missing.to_deposit_tx()));
+        assert!(!tx.is_retry_of(&later.to_withdrawal_tx()));
+        assert!(!tx.is_retry_of(&later.to_transfer_tx(ClientId::new(3))));
+        let other_amount = TransactionMeta {
+            amount: crate::fixed!(UnsignedAssetCount, "4"),
+            ..later
+        };
+        assert!(!tx.is_retry_of(&other_amount.to_deposit_tx()));
+    }
+
+    #[test]
+    fn credit_limit_allows_negative_available() {
+        let mut balance = AccountBalance::new_with(
+            crate::fixed!(UnsignedAssetCount, "1"),
+            crate::fixed!(UnsignedAssetCount, "0"),
+        )
+        .unwrap();
+        let two = crate::fixed!(UnsignedAssetCount, "2");
+        assert!(!balance.can_spend(two));
+        assert_eq!(balance.dec_available(two), Err(BalanceUpdateError));
+
+        balance
+            .set_credit_limit(crate::fixed!(UnsignedAssetCount, "1.5"))
+            .unwrap();
+        assert!(balance.can_spend(two));
+        balance.move_available_to_held(two).unwrap();
+        assert_eq!(balance.available(), crate::fixed!(SignedAssetCount, "-1"));
+        assert_eq!(balance.total(), crate::fixed!(SignedAssetCount, "1"));
+        assert!(!balance.can_spend(crate::fixed!(UnsignedAssetCount, "0.6")));
+
+        assert_eq!(
+            balance.set_credit_limit(crate::fixed!(UnsignedAssetCount, "0.5")),
+            Err(BalanceUpdateError)
+        );
+        assert_eq!(
+            balance.credit_limit(),
+            crate::fixed!(UnsignedAssetCount, "1.5")
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "u128-asset-count"))]
+    fn signed_from_unsigned() {
+        let actual =
+            SignedAssetCount::try_from(crate::fixed!(UnsignedAssetCount, "1844674407370955.1615"));
+        let expected = crate::fixed!(SignedAssetCount, "1844674407370955.1615");
+        assert_eq!(actual, Ok(expected));
+    }
+
+    #[test]
+    fn unsigned_from_signed() {
+        let positive = crate::fixed!(SignedAssetCount, "1.5");
+        assert_eq!(
+            UnsignedAssetCount::try_from(positive),
+            Ok(crate::fixed!(UnsignedAssetCount, "1.5"))
+        );
+        let negative = crate::fixed!(SignedAssetCount, "-1.5");
+        assert!(UnsignedAssetCount::try_from(negative).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "u128-asset-count"))]
+    fn unsigned_from_signed_too_large() {
+        let too_large = crate::fixed!(SignedAssetCount, "1844674407370955.1616");
+        assert!(UnsignedAssetCount::try_from(too_large).is_err());
+    }
+
+    #[test]
+    fn signed_neg_and_abs() {
+        let value = crate::fixed!(SignedAssetCount, "-2.5");
+        assert!(value.is_negative());
+        assert_eq!(
+            value.checked_neg(),
+            Some(crate::fixed!(SignedAssetCount, "2.5"))
+        );
+        assert_eq!(value.abs(), Some(crate::fixed!(UnsignedAssetCount, "2.5")));
+        assert_eq!(value.to_string(), "-2.5000");
+        assert_eq!("-2.5".parse(), Ok(value));
+    }
+
+    #[test]
+    #[cfg(not(feature = "u128-asset-count"))]
+    fn sum_unsigned_into_signed() {
+        use crate::fixed_decimal::CheckedIteratorExt;
+
+        let max = crate::fixed!(UnsignedAssetCount, "1844674407370955.1615");
+        let narrow: Option<UnsignedAssetCount> = [max, max].iter().checked_sum();
+        assert_eq!(narrow, None);
+        let wide: Option<SignedAssetCount> = [max, max].iter().copied().checked_sum();
+        assert_eq!(
+            wide,
+            Some(crate::fixed!(SignedAssetCount, "3689348814741910.3230"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "u128-asset-count")]
+    fn u128_backing_exceeds_u64() {
+        let large = crate::fixed!(UnsignedAssetCount, "1844674407370955.1616");
+        let balance = AccountBalance::new_with(large, large).unwrap();
+        assert_eq!(
+            balance.total(),
+            crate::fixed!(SignedAssetCount, "3689348814741910.3232")
+        );
+    }
 }
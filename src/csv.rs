@@ -1,5 +1,6 @@
 use crate::core::{
-    cmd, Account, ClientId, Command, TransactionId, TransactionMeta, UnsignedAssetCount,
+    cmd, Account, AccountBalance, AccountStatus, AssetId, ClientId, Command, SignedAssetCount,
+    Timestamp, TransactionId, TransactionMeta, UnsignedAssetCount,
 };
 use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
@@ -16,66 +17,220 @@ use thiserror::Error;
 struct CommandRecord {
     r#type: CommandType,
     client: ClientId,
-    tx: TransactionId,
+    /// Transaction id, required for all the commands except operator commands.
+    tx: Option<TransactionId>,
     amount: Option<UnsignedAssetCount>,
+    /// Optional asset column, defaults to [AssetId::DEFAULT].
+    ///
+    /// Disputes and settlements always apply to the asset of the referenced
+    /// transaction, so it is ignored for these commands.
+    #[serde(default)]
+    asset: Option<AssetId>,
+    /// Optional operator column, required for operator commands (`unlock`,
+    /// `freeze`, `close`, `fee`). For these commands, `client` is the target account.
+    #[serde(default)]
+    operator: Option<ClientId>,
+    /// Optional recipient column, required for transfers. For transfers,
+    /// `client` is the sender.
+    #[serde(default)]
+    to: Option<ClientId>,
+    /// Optional timestamp column, in seconds since the Unix epoch.
+    #[serde(default)]
+    timestamp: Option<Timestamp>,
 }
 
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
-#[error("missing deposit amount")]
-pub struct FromDepositRecordError;
+pub enum FromDepositRecordError {
+    #[error("missing deposit transaction id")]
+    MissingTx,
+    #[error("missing deposit amount")]
+    MissingAmount,
+}
 
 impl TryFrom<CommandRecord> for cmd::Deposit {
     type Error = FromDepositRecordError;
 
     fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
         Ok(Self(TransactionMeta {
-            id: value.tx,
+            id: value.tx.ok_or(FromDepositRecordError::MissingTx)?,
             client: value.client,
-            amount: value.amount.ok_or(FromDepositRecordError)?,
+            timestamp: value.timestamp,
+            asset: value.asset.unwrap_or_default(),
+            amount: value.amount.ok_or(FromDepositRecordError::MissingAmount)?,
         }))
     }
 }
 
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
-#[error("missing withdrawal amount")]
-pub struct FromWithdrawalRecordError;
+pub enum FromWithdrawalRecordError {
+    #[error("missing withdrawal transaction id")]
+    MissingTx,
+    #[error("missing withdrawal amount")]
+    MissingAmount,
+}
 
 impl TryFrom<CommandRecord> for cmd::Withdrawal {
     type Error = FromWithdrawalRecordError;
 
     fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
         Ok(Self(TransactionMeta {
-            id: value.tx,
+            id: value.tx.ok_or(FromWithdrawalRecordError::MissingTx)?,
             client: value.client,
-            amount: value.amount.ok_or(FromWithdrawalRecordError)?,
+            timestamp: value.timestamp,
+            asset: value.asset.unwrap_or_default(),
+            amount: value
+                .amount
+                .ok_or(FromWithdrawalRecordError::MissingAmount)?,
         }))
     }
 }
 
-impl From<CommandRecord> for cmd::Dispute {
-    fn from(value: CommandRecord) -> Self {
-        Self {
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FromTransferRecordError {
+    #[error("missing transfer transaction id")]
+    MissingTx,
+    #[error("missing transfer amount")]
+    MissingAmount,
+    #[error("missing transfer recipient")]
+    MissingRecipient,
+}
+
+impl TryFrom<CommandRecord> for cmd::Transfer {
+    type Error = FromTransferRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            meta: TransactionMeta {
+                id: value.tx.ok_or(FromTransferRecordError::MissingTx)?,
+                client: value.client,
+                timestamp: value.timestamp,
+                asset: value.asset.unwrap_or_default(),
+                amount: value.amount.ok_or(FromTransferRecordError::MissingAmount)?,
+            },
+            to: value.to.ok_or(FromTransferRecordError::MissingRecipient)?,
+        })
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing disputed transaction id")]
+pub struct FromDisputeRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Dispute {
+    type Error = FromDisputeRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
             client: value.client,
-            tx: value.tx,
-        }
+            timestamp: value.timestamp,
+            tx: value.tx.ok_or(FromDisputeRecordError)?,
+            amount: value.amount,
+        })
     }
 }
 
-impl From<CommandRecord> for cmd::Resolve {
-    fn from(value: CommandRecord) -> Self {
-        Self {
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing resolved transaction id")]
+pub struct FromResolveRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Resolve {
+    type Error = FromResolveRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
             client: value.client,
-            tx: value.tx,
-        }
+            timestamp: value.timestamp,
+            tx: value.tx.ok_or(FromResolveRecordError)?,
+            amount: value.amount,
+        })
     }
 }
 
-impl From<CommandRecord> for cmd::Chargeback {
-    fn from(value: CommandRecord) -> Self {
-        Self {
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing charged back transaction id")]
+pub struct FromChargebackRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Chargeback {
+    type Error = FromChargebackRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            client: value.client,
+            timestamp: value.timestamp,
+            tx: value.tx.ok_or(FromChargebackRecordError)?,
+            amount: value.amount,
+        })
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing unlock operator")]
+pub struct FromUnlockRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Unlock {
+    type Error = FromUnlockRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            operator: value.operator.ok_or(FromUnlockRecordError)?,
             client: value.client,
+            timestamp: value.timestamp,
+        })
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing freeze operator")]
+pub struct FromFreezeRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Freeze {
+    type Error = FromFreezeRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            operator: value.operator.ok_or(FromFreezeRecordError)?,
+            client: value.client,
+            timestamp: value.timestamp,
+        })
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+#[error("missing close operator")]
+pub struct FromCloseRecordError;
+
+impl TryFrom<CommandRecord> for cmd::Close {
+    type Error = FromCloseRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            operator: value.operator.ok_or(FromCloseRecordError)?,
+            client: value.client,
+            timestamp: value.timestamp,
+        })
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FromFeeRecordError {
+    #[error("missing fee operator")]
+    MissingOperator,
+    #[error("missing fee amount")]
+    MissingAmount,
+}
+
+impl TryFrom<CommandRecord> for cmd::Fee {
+    type Error = FromFeeRecordError;
+
+    fn try_from(value: CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            operator: value.operator.ok_or(FromFeeRecordError::MissingOperator)?,
+            client: value.client,
+            asset: value.asset.unwrap_or_default(),
+            amount: value.amount.ok_or(FromFeeRecordError::MissingAmount)?,
             tx: value.tx,
-        }
+            timestamp: value.timestamp,
+        })
     }
 }
 
@@ -85,6 +240,22 @@ pub enum FromCommandRecordError {
     Deposit(#[from] FromDepositRecordError),
     #[error("invalid record for the type `withdrawal`")]
     Withdrawal(#[from] FromWithdrawalRecordError),
+    #[error("invalid record for the type `transfer`")]
+    Transfer(#[from] FromTransferRecordError),
+    #[error("invalid record for the type `dispute`")]
+    Dispute(#[from] FromDisputeRecordError),
+    #[error("invalid record for the type `resolve`")]
+    Resolve(#[from] FromResolveRecordError),
+    #[error("invalid record for the type `chargeback`")]
+    Chargeback(#[from] FromChargebackRecordError),
+    #[error("invalid record for the type `unlock`")]
+    Unlock(#[from] FromUnlockRecordError),
+    #[error("invalid record for the type `freeze`")]
+    Freeze(#[from] FromFreezeRecordError),
+    #[error("invalid record for the type `close`")]
+    Close(#[from] FromCloseRecordError),
+    #[error("invalid record for the type `fee`")]
+    Fee(#[from] FromFeeRecordError),
 }
 
 impl TryFrom<CommandRecord> for Command {
@@ -94,9 +265,14 @@ impl TryFrom<CommandRecord> for Command {
         let cmd = match record.r#type {
             CommandType::Deposit => Self::Deposit(record.try_into()?),
             CommandType::Withdrawal => Self::Withdrawal(record.try_into()?),
-            CommandType::Dispute => Self::Dispute(record.into()),
-            CommandType::Resolve => Self::Resolve(record.into()),
-            CommandType::Chargeback => Self::Chargeback(record.into()),
+            CommandType::Transfer => Self::Transfer(record.try_into()?),
+            CommandType::Dispute => Self::Dispute(record.try_into()?),
+            CommandType::Resolve => Self::Resolve(record.try_into()?),
+            CommandType::Chargeback => Self::Chargeback(record.try_into()?),
+            CommandType::Unlock => Self::Unlock(record.try_into()?),
+            CommandType::Freeze => Self::Freeze(record.try_into()?),
+            CommandType::Close => Self::Close(record.try_into()?),
+            CommandType::Fee => Self::Fee(record.try_into()?),
         };
         Ok(cmd)
     }
@@ -107,9 +283,14 @@ impl TryFrom<CommandRecord> for Command {
 enum CommandType {
     Deposit,
     Withdrawal,
+    Transfer,
     Dispute,
     Resolve,
     Chargeback,
+    Unlock,
+    Freeze,
+    Close,
+    Fee,
 }
 
 /// A wrapper providing a higher level API to read transaction commands from a
@@ -131,7 +312,7 @@ impl<R: io::Read> CsvCommandReader<R> {
     /// If a row contains a syntax error, it is returned to allow the app
     /// to display an error message. You can continue the iteration after the
     /// error without any issue.
-    pub fn commands(&mut self) -> CsvCommandIter<R> {
+    pub fn commands(&mut self) -> CsvCommandIter<'_, R> {
         let inner = self.inner.deserialize::<CommandRecord>();
         CsvCommandIter { inner }
     }
@@ -192,32 +373,107 @@ impl<'r, R: io::Read + 'r> Iterator for CsvCommandIter<'r, R> {
 #[derive(Debug, Serialize, Deserialize)]
 struct AccountRecord {
     client: ClientId,
-    available: UnsignedAssetCount,
+    /// Negative if the account uses its credit limit.
+    available: SignedAssetCount,
     held: UnsignedAssetCount,
-    total: UnsignedAssetCount,
+    total: SignedAssetCount,
     locked: bool,
+    /// Only present with [AccountColumns::asset].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asset: Option<AssetId>,
+    /// Name of the account status, see [AccountStatus::name].
+    ///
+    /// Only present with [AccountColumns::status].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'static str>,
+    /// Reason of the lock for accounts locked following a transaction, e.g.
+    /// `chargeback #2`. Empty otherwise.
+    ///
+    /// Only present with [AccountColumns::status].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lock_reason: Option<Option<String>>,
+    /// Fees paid so far, not included in the total.
+    ///
+    /// Only present with [AccountColumns::fees].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fees: Option<UnsignedAssetCount>,
+    /// Fees charged without sufficient available assets, not paid yet.
+    ///
+    /// Only present with [AccountColumns::fees].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_debt: Option<UnsignedAssetCount>,
 }
 
-impl TryFrom<Account> for AccountRecord {
-    type Error = ();
-
-    fn try_from(value: Account) -> Result<Self, Self::Error> {
-        Ok(Self {
-            client: value.client,
-            available: value.balance.available(),
-            held: value.balance.held(),
-            total: value.balance.total(),
-            locked: value.locked,
-        })
+impl AccountRecord {
+    /// Create the record for the balance of `account` in the provided asset.
+    fn new(
+        account: &Account,
+        asset: AssetId,
+        balance: AccountBalance,
+        columns: AccountColumns,
+    ) -> Self {
+        Self {
+            client: account.client,
+            available: balance.available(),
+            held: balance.held(),
+            total: balance.total(),
+            locked: account.is_locked(),
+            asset: if columns.asset { Some(asset) } else { None },
+            status: if columns.status {
+                Some(account.status.name())
+            } else {
+                None
+            },
+            lock_reason: if columns.status {
+                match account.status {
+                    AccountStatus::Locked { reason, by_tx } => {
+                        Some(Some(format!("{} #{}", reason, by_tx)))
+                    }
+                    _ => Some(None),
+                }
+            } else {
+                None
+            },
+            fees: if columns.fees {
+                Some(balance.fees())
+            } else {
+                None
+            },
+            fee_debt: if columns.fees {
+                Some(balance.fee_debt())
+            } else {
+                None
+            },
+        }
     }
 }
 
+/// Optional columns of the account output.
+///
+/// They are all disabled by default, for the legacy
+/// `client,available,held,total,locked` output.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AccountColumns {
+    /// `asset` column: asset of the balance reported by the row.
+    ///
+    /// Rows of multi-asset accounts can't be told apart without it.
+    pub asset: bool,
+    /// `status` and `lock_reason` columns: see [AccountStatus].
+    ///
+    /// The legacy `locked` column is `true` for any status other than `active`.
+    pub status: bool,
+    /// `fees` and `fee_debt` columns: fees paid so far and fees not paid yet,
+    /// in the asset of the row.
+    pub fees: bool,
+}
+
 /// Helper struct to provide a higher-level API to output account states.
 ///
 /// Note that it does not write the CSV header row automatically, you must call
 /// `.write_headers` manually.
 pub struct CsvAccountWriter<W: io::Write> {
     inner: csv::Writer<W>,
+    columns: AccountColumns,
 }
 
 impl<W: io::Write> CsvAccountWriter<W> {
@@ -225,7 +481,17 @@ impl<W: io::Write> CsvAccountWriter<W> {
         let inner = csv::WriterBuilder::new()
             .has_headers(false)
             .from_writer(writer);
-        Self { inner }
+        Self {
+            inner,
+            columns: AccountColumns::default(),
+        }
+    }
+
+    /// Set the optional columns to write, see [AccountColumns].
+    ///
+    /// This must be called before writing any row.
+    pub fn set_columns(&mut self, columns: AccountColumns) {
+        self.columns = columns;
     }
 
     /// Write a header line.
@@ -233,21 +499,38 @@ impl<W: io::Write> CsvAccountWriter<W> {
     /// This must be called explicitly to support empty collections.
     /// See <https://github.com/BurntSushi/rust-csv/issues/161>
     pub fn write_headers(&mut self) -> csv::Result<()> {
-        self.inner.write_record(std::array::IntoIter::new([
-            "client",
-            "available",
-            "held",
-            "total",
-            "locked",
-        ]))
+        let mut headers = vec!["client", "available", "held", "total", "locked"];
+        if self.columns.asset {
+            headers.push("asset");
+        }
+        if self.columns.status {
+            headers.extend(["status", "lock_reason"]);
+        }
+        if self.columns.fees {
+            headers.extend(["fees", "fee_debt"]);
+        }
+        self.inner.write_record(headers)
     }
 
-    /// Write a single CSV row.
+    /// Write the CSV rows for an account: one row per asset.
+    ///
+    /// Accounts without any balance get a single empty row for the default
+    /// asset, so every known client is present in the output.
     pub fn write(&mut self, account: Account) -> csv::Result<()> {
-        let record: AccountRecord = account
-            .try_into()
-            .expect("failed to compute account record");
-        self.inner.serialize(record)
+        if account.balances.is_empty() {
+            let record = AccountRecord::new(
+                &account,
+                AssetId::DEFAULT,
+                AccountBalance::new(),
+                self.columns,
+            );
+            return self.inner.serialize(record);
+        }
+        for (asset, balance) in account.balances.iter() {
+            let record = AccountRecord::new(&account, *asset, *balance, self.columns);
+            self.inner.serialize(record)?;
+        }
+        Ok(())
     }
 
     /// Consume the iterator and write one row per item.
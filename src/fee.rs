@@ -0,0 +1,242 @@
+use crate::core::{AssetCountRepr, AssetId, ClientId, TransactionId, UnsignedAssetCount};
+use crate::fixed_decimal::{ArithmeticError, FixedDecimal, RoundingMode};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Proportional part of a fee, as a fraction of the transaction amount.
+///
+/// For example, `0.015` is a fee of 1.5%.
+pub type FeeRate = FixedDecimal<AssetCountRepr, 6>;
+
+/// Fee charged for a command: a flat amount plus a percentage of the
+/// transaction amount.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Fee {
+    pub flat: UnsignedAssetCount,
+    pub rate: FeeRate,
+}
+
+impl Fee {
+    /// Compute the fee for the provided transaction amount.
+    ///
+    /// The percentage is rounded to the precision of the asset with `mode`.
+    pub fn compute(
+        &self,
+        amount: UnsignedAssetCount,
+        mode: RoundingMode,
+    ) -> Result<UnsignedAssetCount, ArithmeticError> {
+        let proportional = UnsignedAssetCount::new(amount.get().checked_mul(&self.rate, mode)?);
+        proportional
+            .checked_add(self.flat)
+            .ok_or(ArithmeticError::Overflow)
+    }
+}
+
+/// Commands charged by a [FeeSchedule].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum FeeKind {
+    Deposit,
+    Withdrawal,
+    Transfer,
+    Chargeback,
+}
+
+impl FeeKind {
+    /// Get the name of the command type, as used in the CSV input.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Transfer => "transfer",
+            Self::Chargeback => "chargeback",
+        }
+    }
+}
+
+impl fmt::Display for FeeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("unknown fee kind {:?}, expected `deposit`, `withdrawal`, `transfer` or `chargeback`", .0)]
+pub struct ParseFeeKindError(String);
+
+impl FromStr for FeeKind {
+    type Err = ParseFeeKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deposit" => Ok(Self::Deposit),
+            "withdrawal" => Ok(Self::Withdrawal),
+            "transfer" => Ok(Self::Transfer),
+            "chargeback" => Ok(Self::Chargeback),
+            _ => Err(ParseFeeKindError(s.to_string())),
+        }
+    }
+}
+
+/// How to handle fees exceeding the available assets of the account.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InsufficientFeeFundsPolicy {
+    /// Reject the command charging the fee.
+    RejectParent,
+    /// Accept the command and pay the fee with all the available assets. The
+    /// remaining part is recorded as a fee debt, paid by the next deposits or
+    /// incoming transfers.
+    Debt,
+}
+
+impl Default for InsufficientFeeFundsPolicy {
+    fn default() -> Self {
+        Self::RejectParent
+    }
+}
+
+/// Fees charged automatically by the account service.
+///
+/// The fee of a command is charged to the client issuing it (the sender for
+/// transfers, the claimant for chargebacks), in the asset of the transaction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FeeSchedule {
+    pub deposit: Fee,
+    pub withdrawal: Fee,
+    pub transfer: Fee,
+    pub chargeback: Fee,
+    /// Rounding mode for the percentage part of the fees.
+    pub rounding: RoundingMode,
+    /// See [InsufficientFeeFundsPolicy].
+    pub insufficient_funds: InsufficientFeeFundsPolicy,
+}
+
+impl FeeSchedule {
+    /// Get the fee for the provided command type.
+    pub fn fee(&self, kind: FeeKind) -> Fee {
+        match kind {
+            FeeKind::Deposit => self.deposit,
+            FeeKind::Withdrawal => self.withdrawal,
+            FeeKind::Transfer => self.transfer,
+            FeeKind::Chargeback => self.chargeback,
+        }
+    }
+
+    /// Get a mutable reference to the fee for the provided command type.
+    pub fn fee_mut(&mut self, kind: FeeKind) -> &mut Fee {
+        match kind {
+            FeeKind::Deposit => &mut self.deposit,
+            FeeKind::Withdrawal => &mut self.withdrawal,
+            FeeKind::Transfer => &mut self.transfer,
+            FeeKind::Chargeback => &mut self.chargeback,
+        }
+    }
+
+    /// Compute the fee for a command of the provided type and amount.
+    pub fn compute(
+        &self,
+        kind: FeeKind,
+        amount: UnsignedAssetCount,
+    ) -> Result<UnsignedAssetCount, ArithmeticError> {
+        self.fee(kind).compute(amount, self.rounding)
+    }
+}
+
+impl Default for FeeSchedule {
+    /// No fees, percentages are rounded half to even.
+    fn default() -> Self {
+        Self {
+            deposit: Fee::default(),
+            withdrawal: Fee::default(),
+            transfer: Fee::default(),
+            chargeback: Fee::default(),
+            rounding: RoundingMode::HalfEven,
+            insufficient_funds: InsufficientFeeFundsPolicy::default(),
+        }
+    }
+}
+
+/// A fee charged to an account.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FeeTransaction {
+    /// Client paying the fee.
+    pub client: ClientId,
+    pub asset: AssetId,
+    /// Total fee amount, including the debt.
+    pub amount: UnsignedAssetCount,
+    /// Part of the amount added to the fee debt of the account.
+    pub debt: UnsignedAssetCount,
+    pub source: FeeSource,
+}
+
+/// Origin of a [FeeTransaction].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FeeSource {
+    /// Charged by the fee schedule for the transaction `parent`.
+    Schedule {
+        kind: FeeKind,
+        parent: TransactionId,
+    },
+    /// Charged by an operator with a `fee` command, optionally for the
+    /// transaction `parent`.
+    Command {
+        operator: ClientId,
+        parent: Option<TransactionId>,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fee, FeeKind, FeeRate, FeeSchedule, ParseFeeKindError};
+    use crate::core::UnsignedAssetCount;
+    use crate::fixed_decimal::RoundingMode;
+
+    #[test]
+    fn flat_and_rate() {
+        let fee = Fee {
+            flat: crate::fixed!(UnsignedAssetCount, "0.5"),
+            rate: crate::fixed!(FeeRate, "0.01"),
+        };
+        let actual = fee.compute(crate::fixed!(UnsignedAssetCount, "20"), RoundingMode::Exact);
+        assert_eq!(actual, Ok(crate::fixed!(UnsignedAssetCount, "0.7")));
+    }
+
+    #[test]
+    fn rate_is_rounded() {
+        let fee = Fee {
+            flat: crate::fixed!(UnsignedAssetCount, "0"),
+            rate: crate::fixed!(FeeRate, "0.015"),
+        };
+        let amount = crate::fixed!(UnsignedAssetCount, "0.0101");
+        assert!(fee.compute(amount, RoundingMode::Exact).is_err());
+        assert_eq!(
+            fee.compute(amount, RoundingMode::HalfEven),
+            Ok(crate::fixed!(UnsignedAssetCount, "0.0002"))
+        );
+        assert_eq!(
+            fee.compute(amount, RoundingMode::TowardZero),
+            Ok(crate::fixed!(UnsignedAssetCount, "0.0001"))
+        );
+    }
+
+    #[test]
+    fn default_schedule_is_free() {
+        let schedule = FeeSchedule::default();
+        assert_eq!(
+            schedule.compute(
+                FeeKind::Withdrawal,
+                crate::fixed!(UnsignedAssetCount, "1000")
+            ),
+            Ok(crate::fixed!(UnsignedAssetCount, "0"))
+        );
+    }
+
+    #[test]
+    fn parse_fee_kind() {
+        assert_eq!("chargeback".parse(), Ok(FeeKind::Chargeback));
+        assert_eq!(
+            "dispute".parse::<FeeKind>(),
+            Err(ParseFeeKindError("dispute".to_string()))
+        );
+    }
+}
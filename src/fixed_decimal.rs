@@ -1,6 +1,11 @@
-use num::{Bounded, CheckedAdd, CheckedMul, CheckedSub, Integer};
+use num::traits::CheckedNeg;
+use num::{
+    BigInt, CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromPrimitive, Integer, ToPrimitive,
+};
+use serde::de::Unexpected;
 use serde::{Deserialize, Serialize, Serializer};
-use std::fmt::{Debug, Display, Formatter};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{Debug, Display, Formatter, Write};
 use std::marker::PhantomData;
 use std::str::FromStr;
 use thiserror::Error;
@@ -14,8 +19,9 @@ use thiserror::Error;
 /// You can control the underlying integral type.
 ///
 /// The underlying type is bound by [num::Integer], it gives you the flexibility to use your
-/// own custom integer type (e.g. [num::BigInt]. Note however that the type must at least support
-/// representing the numbers in `0..=10`, it means that using `NonZero*` types is not possible yet.
+/// own custom integer type (e.g. [num::BigInt] for values without any bound). Note however that
+/// the type must at least support representing the numbers in `0..=10`, it means that using
+/// `NonZero*` types is not possible yet.
 ///
 /// # Examples
 ///
@@ -27,6 +33,7 @@ use thiserror::Error;
 /// | `FixedDecimal<i16, 4>` | -3.2768   | 3.2767                                  | 1e-4
 /// | `FixedDecimal<i16, 5>` | -0.32768  | 0.32767                                 | 1e-5
 /// | `FixedDecimal<i16, 6>` | -0.032768 | 0.032767                                | 1e-6
+/// | `FixedDecimal<BigInt, 4>` | unbounded | unbounded                            | 1e-4
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Default)]
 pub struct FixedDecimal<T: Integer, const PRECISION: u8>(T);
 
@@ -51,188 +58,1461 @@ where
     /// Returns `None` if a unit cannot be represented: e.g. `FixedPoint<u8, 3>`
     /// can only represent values in `[0, 0.255]`.
     pub fn fractions_per_unit() -> Option<T> {
-        let ten: T = 10.into();
-        let mut fractions = T::one();
-        for _ in 0..PRECISION {
-            fractions = fractions.checked_mul(&ten)?;
+        pow10(PRECISION)
+    }
+}
+
+impl<T: Integer + Debug, const PRECISION: u8> Debug for FixedDecimal<T, PRECISION> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FixedDecimal({:?}e-{})", self.fractions(), PRECISION)
+    }
+}
+
+impl<T: Integer + CheckedAdd, const PRECISION: u8> FixedDecimal<T, PRECISION> {
+    pub fn checked_add(&self, v: &Self) -> Option<Self> {
+        num::CheckedAdd::checked_add(&self.0, &v.0).map(Self::from_fractions)
+    }
+}
+
+impl<T: Integer + CheckedSub, const PRECISION: u8> FixedDecimal<T, PRECISION> {
+    pub fn checked_sub(&self, v: &Self) -> Option<Self> {
+        num::CheckedSub::checked_sub(&self.0, &v.0).map(Self::from_fractions)
+    }
+}
+
+impl<T: Integer + CheckedNeg + Clone, const PRECISION: u8> FixedDecimal<T, PRECISION> {
+    /// Negate this value.
+    ///
+    /// Returns `None` if the result cannot be represented: e.g. the minimum value of a signed
+    /// type, or any non-zero value of an unsigned type.
+    pub fn checked_neg(&self) -> Option<Self> {
+        CheckedNeg::checked_neg(&self.0).map(Self::from_fractions)
+    }
+
+    /// Get the absolute value.
+    ///
+    /// Returns `None` if the result cannot be represented (minimum value of a signed type).
+    pub fn checked_abs(&self) -> Option<Self> {
+        if self.0 < T::zero() {
+            self.checked_neg()
+        } else {
+            Some(Self::from_fractions(self.0.clone()))
+        }
+    }
+}
+
+/// Build a fixed decimal value from a literal, checked at compile time.
+///
+/// The first argument is the target type, it must provide a `const fn from_literal(&str)`:
+/// it is the case for [FixedDecimal] with primitive backing types and
+/// [crate::core::UnsignedAssetCount]. The value is evaluated as a constant, so an invalid
+/// literal (syntax, precision or range) is a build error.
+///
+/// # Example
+///
+/// ```
+/// use txdemo::fixed;
+/// use txdemo::fixed_decimal::FixedDecimal;
+///
+/// let value = fixed!(FixedDecimal<u64, 4>, "1.2345");
+/// assert_eq!(value, FixedDecimal::from_fractions(12345));
+/// ```
+///
+/// ```compile_fail
+/// use txdemo::fixed;
+/// use txdemo::fixed_decimal::FixedDecimal;
+///
+/// // Too many fractional digits
+/// let value = fixed!(FixedDecimal<u64, 4>, "1.23456");
+/// ```
+#[macro_export]
+macro_rules! fixed {
+    ($typ:ty, $literal:literal) => {{
+        const VALUE: $typ = <$typ>::from_literal($literal);
+        VALUE
+    }};
+}
+
+macro_rules! impl_from_literal {
+    ($($typ:ty),+$(,)?) => {
+        $(
+            impl<const PRECISION: u8> FixedDecimal<$typ, PRECISION> {
+                /// Parse a decimal literal using the strict syntax, in a `const` context.
+                ///
+                /// This is intended for constants such as policy limits, see also [fixed!].
+                ///
+                /// # Panics
+                ///
+                /// Panics if the literal is invalid. In a `const` context, this is a build error.
+                pub const fn from_literal(literal: &str) -> Self {
+                    let bytes = literal.as_bytes();
+                    let mut i: usize = 0;
+                    let negative = !bytes.is_empty() && bytes[0] == b'-';
+                    if negative {
+                        if <$typ>::MIN == 0 {
+                            panic!("fixed decimal literal is negative but the type is unsigned");
+                        }
+                        i = 1;
+                    }
+                    let mut fractions: $typ = 0;
+                    let mut has_digit = false;
+                    let mut has_separator = false;
+                    let mut decimal_digits: u8 = 0;
+                    while i < bytes.len() {
+                        let c = bytes[i];
+                        i += 1;
+                        if c == b'.' {
+                            if has_separator {
+                                panic!("fixed decimal literal has multiple decimal separators");
+                            }
+                            has_separator = true;
+                            continue;
+                        }
+                        if !c.is_ascii_digit() {
+                            panic!("fixed decimal literal has an invalid character");
+                        }
+                        has_digit = true;
+                        if has_separator {
+                            if decimal_digits == PRECISION {
+                                panic!("fixed decimal literal exceeds the supported precision");
+                            }
+                            decimal_digits += 1;
+                        }
+                        let digit = (c - b'0') as $typ;
+                        let shifted = match fractions.checked_mul(10) {
+                            Some(shifted) => shifted,
+                            None => panic!("fixed decimal literal is too large"),
+                        };
+                        // Accumulate negative values directly, to support `MIN`
+                        let next = if negative {
+                            shifted.checked_sub(digit)
+                        } else {
+                            shifted.checked_add(digit)
+                        };
+                        fractions = match next {
+                            Some(next) => next,
+                            None => panic!("fixed decimal literal is too large"),
+                        };
+                    }
+                    if !has_digit {
+                        panic!("fixed decimal literal has no digits");
+                    }
+                    while decimal_digits < PRECISION {
+                        fractions = match fractions.checked_mul(10) {
+                            Some(shifted) => shifted,
+                            None => panic!("fixed decimal literal is too large"),
+                        };
+                        decimal_digits += 1;
+                    }
+                    Self(fractions)
+                }
+            }
+        )+
+    };
+}
+
+impl_from_literal!(u16, u32, u64, u128, usize, i16, i32, i64, i128, isize);
+
+/// Strategy to use when the result of an operation cannot be represented exactly.
+///
+/// Rounding always requires an explicit decision from the caller: operations which may lose
+/// precision take a `RoundingMode` parameter. Use [RoundingMode::Exact] to reject any
+/// precision loss.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RoundingMode {
+    /// Fail with [ArithmeticError::Inexact] if rounding would be required.
+    Exact,
+    /// Round toward zero (truncate the discarded digits).
+    TowardZero,
+    /// Round toward negative infinity.
+    Floor,
+    /// Round toward positive infinity.
+    Ceiling,
+    /// Round to the nearest value, ties are rounded away from zero.
+    HalfUp,
+    /// Round to the nearest value, ties are rounded to the even neighbor ("banker's rounding").
+    HalfEven,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ArithmeticError {
+    #[error("the magnitude of the result is too large")]
+    Overflow,
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("the result cannot be represented without rounding")]
+    Inexact,
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<u8> + Clone,
+{
+    /// Multiply this value by an integer.
+    ///
+    /// This operation never requires rounding, it only fails on overflow.
+    pub fn checked_mul_int(&self, v: &T) -> Option<Self> {
+        self.0.checked_mul(v).map(Self::from_fractions)
+    }
+
+    /// Divide this value by an integer, using the provided rounding mode.
+    pub fn checked_div_int(&self, v: &T, mode: RoundingMode) -> Result<Self, ArithmeticError> {
+        div_shifted(&self.0, v, 0, mode).map(Self::from_fractions)
+    }
+
+    /// Multiply this value by a decimal of any precision, using the provided rounding mode.
+    ///
+    /// The result has the precision of `self`.
+    ///
+    /// The exact product is computed without intermediate rounding. The intermediate values
+    /// stay below the magnitude of the result as long as `10 ** (2 * RHS_PRECISION)` fits in `T`;
+    /// otherwise the computation may report an overflow even if the result is representable.
+    pub fn checked_mul<const RHS_PRECISION: u8>(
+        &self,
+        v: &FixedDecimal<T, RHS_PRECISION>,
+        mode: RoundingMode,
+    ) -> Result<Self, ArithmeticError> {
+        mul_unshifted(&self.0, &v.0, RHS_PRECISION, mode).map(Self::from_fractions)
+    }
+
+    /// Divide this value by a decimal of any precision, using the provided rounding mode.
+    ///
+    /// The result has the precision of `self`.
+    pub fn checked_div<const RHS_PRECISION: u8>(
+        &self,
+        v: &FixedDecimal<T, RHS_PRECISION>,
+        mode: RoundingMode,
+    ) -> Result<Self, ArithmeticError> {
+        div_shifted(&self.0, &v.0, RHS_PRECISION, mode).map(Self::from_fractions)
+    }
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + From<u8> + Clone,
+{
+    /// Convert this value to another precision, without any precision loss.
+    ///
+    /// Widening the precision never loses information: it only fails with
+    /// [ArithmeticError::Overflow] if the result overflows `T`. Narrowing the
+    /// precision fails with [ArithmeticError::Inexact] if the value has non-zero
+    /// digits which cannot be represented with the new precision; use
+    /// [FixedDecimal::rescale_with] to narrow with rounding.
+    pub fn rescale<const NEW_PRECISION: u8>(
+        &self,
+    ) -> Result<FixedDecimal<T, NEW_PRECISION>, ArithmeticError> {
+        self.rescale_with(RoundingMode::Exact)
+    }
+
+    /// Convert this value to another precision, using the provided rounding
+    /// mode if the precision is narrowed.
+    pub fn rescale_with<const NEW_PRECISION: u8>(
+        &self,
+        mode: RoundingMode,
+    ) -> Result<FixedDecimal<T, NEW_PRECISION>, ArithmeticError> {
+        rescale_fractions(&self.0, PRECISION, NEW_PRECISION, mode).map(FixedDecimal::from_fractions)
+    }
+}
+
+/// Convert `fractions` from `precision` to `new_precision`, using the provided rounding
+/// mode if the precision is narrowed.
+fn rescale_fractions<T>(
+    fractions: &T,
+    precision: u8,
+    new_precision: u8,
+    mode: RoundingMode,
+) -> Result<T, ArithmeticError>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + From<u8> + Clone,
+{
+    if new_precision >= precision {
+        pow10::<T>(new_precision - precision)
+            .and_then(|scale| fractions.checked_mul(&scale))
+            .ok_or(ArithmeticError::Overflow)
+    } else {
+        div_pow10(fractions.clone(), precision - new_precision, mode)
+    }
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + Clone,
+{
+    /// Split this value in parts proportional to the provided integer weights.
+    ///
+    /// The parts always sum exactly to the original value: the fractions which cannot be
+    /// divided evenly are assigned one by one to the parts with the largest remainders
+    /// (largest remainder method). Ties are broken in favor of the first parts.
+    ///
+    /// Returns `None` if there are no weights, if a weight is negative, if all the weights
+    /// are zero, or if an intermediate computation overflows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use txdemo::fixed;
+    /// use txdemo::fixed_decimal::FixedDecimal;
+    ///
+    /// let parts = fixed!(FixedDecimal<u64, 2>, "1.00").allocate(&[1, 1, 1]).unwrap();
+    /// assert_eq!(parts, vec![
+    ///     fixed!(FixedDecimal<u64, 2>, "0.34"),
+    ///     fixed!(FixedDecimal<u64, 2>, "0.33"),
+    ///     fixed!(FixedDecimal<u64, 2>, "0.33"),
+    /// ]);
+    /// ```
+    pub fn allocate(&self, weights: &[T]) -> Option<Vec<Self>> {
+        let zero = T::zero();
+        let mut total_weight = T::zero();
+        for weight in weights {
+            if *weight < zero {
+                return None;
+            }
+            total_weight = total_weight.checked_add(weight)?;
+        }
+        if total_weight.is_zero() {
+            return None;
+        }
+        let negative = self.0 < zero;
+        // With `amount = q * total + r`:
+        // `amount * weight / total = q * weight + r * weight / total`
+        let (q, r) = self.0.div_rem(&total_weight);
+        let mut parts: Vec<T> = Vec::with_capacity(weights.len());
+        let mut remainders: Vec<(usize, T)> = Vec::with_capacity(weights.len());
+        let mut allocated = T::zero();
+        for (i, weight) in weights.iter().enumerate() {
+            let (part_q, part_r) = r.checked_mul(weight)?.div_rem(&total_weight);
+            let part = q.checked_mul(weight)?.checked_add(&part_q)?;
+            allocated = allocated.checked_add(&part)?;
+            parts.push(part);
+            remainders.push((i, part_r));
+        }
+        // The remainders have the sign of the amount: sort them by decreasing magnitude.
+        // The sort is stable so ties keep their original order.
+        if negative {
+            remainders.sort_by(|(_, left), (_, right)| left.cmp(right));
+        } else {
+            remainders.sort_by(|(_, left), (_, right)| right.cmp(left));
+        }
+        // There are less leftover fractions than parts
+        let mut leftover = self.0.checked_sub(&allocated)?;
+        for (i, _) in remainders {
+            if leftover.is_zero() {
+                break;
+            }
+            let part = &mut parts[i];
+            if negative {
+                *part = part.checked_sub(&T::one())?;
+                leftover = leftover.checked_add(&T::one())?;
+            } else {
+                *part = part.checked_add(&T::one())?;
+                leftover = leftover.checked_sub(&T::one())?;
+            }
+        }
+        Some(parts.into_iter().map(Self::from_fractions).collect())
+    }
+
+    /// Split this value in parts proportional to the provided decimal weights.
+    ///
+    /// See [FixedDecimal::allocate].
+    pub fn allocate_by<const WEIGHT_PRECISION: u8>(
+        &self,
+        weights: &[FixedDecimal<T, WEIGHT_PRECISION>],
+    ) -> Option<Vec<Self>> {
+        // All the weights share the same scale: allocating by their fractions is equivalent.
+        let weights: Vec<T> = weights.iter().map(|w| w.0.clone()).collect();
+        self.allocate(&weights)
+    }
+
+    /// Split this value in `n` parts as equal as possible.
+    ///
+    /// See [FixedDecimal::allocate]. Returns `None` if `n` is zero.
+    pub fn split(&self, n: usize) -> Option<Vec<Self>> {
+        self.allocate(&vec![T::one(); n])
+    }
+}
+
+impl<T: Integer + Clone, const PRECISION: u8> FixedDecimal<T, PRECISION> {
+    /// Convert this value to another backing type, when the conversion is always lossless
+    /// (e.g. `u64` to `u128`).
+    pub fn cast<U: Integer + From<T>>(&self) -> FixedDecimal<U, PRECISION> {
+        FixedDecimal::from_fractions(U::from(self.0.clone()))
+    }
+
+    /// Convert this value to another backing type (e.g. `u64` to `i64`).
+    ///
+    /// Returns `None` if the value is out of the range of the new backing type.
+    pub fn checked_cast<U: Integer + TryFrom<T>>(&self) -> Option<FixedDecimal<U, PRECISION>> {
+        U::try_from(self.0.clone())
+            .ok()
+            .map(FixedDecimal::from_fractions)
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FromF64Error {
+    #[error("the float is NaN or infinite")]
+    NotFinite,
+    #[error("the float is out of the range of the target type")]
+    OutOfRange,
+    #[error("the float cannot be represented without rounding")]
+    Inexact,
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + FromPrimitive + Clone,
+{
+    /// Convert a float to a fixed decimal, using the provided rounding mode.
+    ///
+    /// The conversion uses the exact binary value of the float. Most decimal fractions are not
+    /// exactly representable as floats (e.g. `0.1` is actually `0.1000000000000000055...`), so
+    /// [RoundingMode::Exact] rejects them: use [RoundingMode::HalfEven] to get the nearest value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use txdemo::fixed;
+    /// use txdemo::fixed_decimal::{FixedDecimal, FromF64Error, RoundingMode};
+    ///
+    /// let exact = FixedDecimal::<u64, 4>::try_from_f64(1.25, RoundingMode::Exact);
+    /// assert_eq!(exact, Ok(fixed!(FixedDecimal<u64, 4>, "1.25")));
+    /// let inexact = FixedDecimal::<u64, 4>::try_from_f64(0.1, RoundingMode::Exact);
+    /// assert_eq!(inexact, Err(FromF64Error::Inexact));
+    /// let nearest = FixedDecimal::<u64, 4>::try_from_f64(0.1, RoundingMode::HalfEven);
+    /// assert_eq!(nearest, Ok(fixed!(FixedDecimal<u64, 4>, "0.1")));
+    /// ```
+    pub fn try_from_f64(value: f64, mode: RoundingMode) -> Result<Self, FromF64Error> {
+        if !value.is_finite() {
+            return Err(FromF64Error::NotFinite);
+        }
+        // `value = mantissa * 2 ** exponent`
+        let bits = value.to_bits();
+        let negative = (bits >> 63) != 0;
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & 0x000f_ffff_ffff_ffff;
+        let (mantissa, exponent) = if biased_exponent == 0 {
+            // Subnormal (or zero)
+            (fraction, -1074)
+        } else {
+            (fraction | 0x0010_0000_0000_0000, biased_exponent - 1075)
+        };
+        // Compute `mantissa * 2 ** exponent * 10 ** PRECISION` exactly
+        let scaled = BigInt::from(mantissa) * num::pow(BigInt::from(10u8), usize::from(PRECISION));
+        let (q, discarded) = if exponent >= 0 {
+            (scaled << (exponent as usize), Discarded::Zero)
+        } else {
+            let d = BigInt::from(1u8) << ((-exponent) as usize);
+            let (q, r) = scaled.div_rem(&d);
+            (q, Discarded::from_remainder(&r, &d))
+        };
+        let q = if negative { -q } else { q };
+        let fractions = round_quotient(q, discarded, negative, mode).map_err(|e| match e {
+            ArithmeticError::Inexact => FromF64Error::Inexact,
+            _ => FromF64Error::OutOfRange,
+        })?;
+        from_big_int(&fractions)
+            .map(Self::from_fractions)
+            .ok_or(FromF64Error::OutOfRange)
+    }
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    /// Convert this value to the nearest float.
+    ///
+    /// This conversion is lossy: floats cannot represent most decimal fractions, and large
+    /// values lose their least significant digits (or become infinite).
+    pub fn to_f64_lossy(&self) -> f64 {
+        // Parsing the decimal representation gives the correctly rounded float.
+        self.with_str(|s| {
+            s.parse()
+                .expect("decimal representation should be a valid float")
+        })
+    }
+}
+
+/// Convert an arbitrary precision integer to `T`.
+///
+/// Returns `None` if the value is out of the range of `T`.
+fn from_big_int<T>(value: &BigInt) -> Option<T>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + FromPrimitive,
+{
+    let negative = value.sign() == num::bigint::Sign::Minus;
+    let (_, digits) = value.to_u32_digits();
+    let mut result = T::zero();
+    // Only required for multi-digit values, so it is never built for small types
+    let mut radix: Option<T> = None;
+    for digit in digits.iter().rev() {
+        if !result.is_zero() {
+            if radix.is_none() {
+                radix = Some(T::from_u64(1 << 32)?);
+            }
+            result = result.checked_mul(radix.as_ref()?)?;
+        }
+        let digit = T::from_u32(*digit)?;
+        // Accumulate negative values directly, to support `MIN`
+        result = if negative {
+            result.checked_sub(&digit)?
+        } else {
+            result.checked_add(&digit)?
+        };
+    }
+    Some(result)
+}
+
+/// Checked counterpart of [std::iter::Sum]: sum an iterator, failing on overflow.
+pub trait CheckedSum<A = Self>: Sized {
+    /// Sum all the items, returns `None` if the total cannot be represented.
+    fn checked_sum<I: Iterator<Item = A>>(iter: I) -> Option<Self>;
+}
+
+/// Checked counterpart of [std::iter::Product]: multiply the items of an iterator, failing
+/// on overflow or if rounding would be required.
+pub trait CheckedProduct<A = Self>: Sized {
+    /// Multiply all the items, returns `None` if the result cannot be represented exactly.
+    fn checked_product<I: Iterator<Item = A>>(iter: I) -> Option<Self>;
+}
+
+/// Extension methods to use [CheckedSum] and [CheckedProduct] directly on iterators.
+///
+/// # Example
+///
+/// ```
+/// use txdemo::fixed;
+/// use txdemo::fixed_decimal::{CheckedIteratorExt, FixedDecimal};
+///
+/// let values = [
+///     fixed!(FixedDecimal<u64, 4>, "1.5"),
+///     fixed!(FixedDecimal<u64, 4>, "2.25"),
+/// ];
+/// let total: Option<FixedDecimal<u64, 4>> = values.iter().checked_sum();
+/// assert_eq!(total, Some(fixed!(FixedDecimal<u64, 4>, "3.75")));
+/// ```
+pub trait CheckedIteratorExt: Iterator + Sized {
+    fn checked_sum<S: CheckedSum<Self::Item>>(self) -> Option<S> {
+        S::checked_sum(self)
+    }
+
+    fn checked_product<P: CheckedProduct<Self::Item>>(self) -> Option<P> {
+        P::checked_product(self)
+    }
+}
+
+impl<I: Iterator> CheckedIteratorExt for I {}
+
+impl<T: Integer + CheckedAdd, const PRECISION: u8> CheckedSum for FixedDecimal<T, PRECISION> {
+    fn checked_sum<I: Iterator<Item = Self>>(mut iter: I) -> Option<Self> {
+        iter.try_fold(Self::from_fractions(T::zero()), |acc, v| {
+            acc.checked_add(&v)
+        })
+    }
+}
+
+impl<'a, T: Integer + CheckedAdd, const PRECISION: u8> CheckedSum<&'a Self>
+    for FixedDecimal<T, PRECISION>
+{
+    fn checked_sum<I: Iterator<Item = &'a Self>>(mut iter: I) -> Option<Self> {
+        iter.try_fold(Self::from_fractions(T::zero()), |acc, v| acc.checked_add(v))
+    }
+}
+
+impl<T, const PRECISION: u8> CheckedProduct for FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<u8> + Clone,
+{
+    fn checked_product<I: Iterator<Item = Self>>(mut iter: I) -> Option<Self> {
+        let one = Self::checked_from_int(T::one())?;
+        iter.try_fold(one, |acc, v| acc.checked_mul(&v, RoundingMode::Exact).ok())
+    }
+}
+
+impl<'a, T, const PRECISION: u8> CheckedProduct<&'a Self> for FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<u8> + Clone,
+{
+    fn checked_product<I: Iterator<Item = &'a Self>>(mut iter: I) -> Option<Self> {
+        let one = Self::checked_from_int(T::one())?;
+        iter.try_fold(one, |acc, v| acc.checked_mul(v, RoundingMode::Exact).ok())
+    }
+}
+
+impl<U: Integer + CheckedAdd, const PRECISION: u8> FixedDecimal<U, PRECISION> {
+    /// Sum values with a narrower backing type into a wider accumulator.
+    ///
+    /// This is useful to total many values without overflowing, e.g. summing
+    /// `FixedDecimal<u64, 4>` values into a `FixedDecimal<u128, 4>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use txdemo::fixed_decimal::FixedDecimal;
+    ///
+    /// let max = FixedDecimal::<u64, 4>::from_fractions(u64::MAX);
+    /// let total = FixedDecimal::<u128, 4>::checked_sum_widening([max, max].iter().copied());
+    /// assert_eq!(total, Some(FixedDecimal::from_fractions(2 * u128::from(u64::MAX))));
+    /// ```
+    pub fn checked_sum_widening<T, I>(mut iter: I) -> Option<Self>
+    where
+        T: Integer + Into<U>,
+        I: Iterator<Item = FixedDecimal<T, PRECISION>>,
+    {
+        iter.try_fold(Self::from_fractions(U::zero()), |acc, v| {
+            acc.checked_add(&Self::from_fractions(v.0.into()))
+        })
+    }
+}
+
+/// Size of the stack buffer used to format values.
+///
+/// It is large enough for any primitive backing type (up to 39 digits for `u128`) as long as
+/// `PRECISION <= 60`. Larger values fall back to a heap buffer.
+const INLINE_FORMAT_LEN: usize = 64;
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    /// Write the decimal representation of this value at the end of `buf`, without allocating.
+    ///
+    /// Returns the written string, or `None` if the buffer is too small.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use txdemo::fixed_decimal::FixedDecimal;
+    ///
+    /// let value = FixedDecimal::<u64, 4>::from_fractions(12345);
+    /// let mut buf = [0u8; 32];
+    /// assert_eq!(value.write_to(&mut buf), Some("1.2345"));
+    /// ```
+    pub fn write_to<'b>(&self, buf: &'b mut [u8]) -> Option<&'b str> {
+        write_fractions(buf, &self.0, PRECISION)
+    }
+
+    /// Call `f` with the decimal representation of this value.
+    ///
+    /// Uses a stack buffer when possible, and only allocates for very large values.
+    fn with_str<R>(&self, f: impl FnOnce(&str) -> R) -> R {
+        with_fractions_str(&self.0, PRECISION, f)
+    }
+}
+
+/// Write the decimal representation of `fractions` at the end of `buf`, with exactly
+/// `precision` fractional digits.
+///
+/// Returns the written string, or `None` if the buffer is too small.
+fn write_fractions<'b, T>(buf: &'b mut [u8], fractions: &T, precision: u8) -> Option<&'b str>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    let negative = *fractions < T::zero();
+    // Fast paths: use native integers if the magnitude fits
+    let start = if negative {
+        match fractions.to_i64() {
+            Some(n) => write_digits(buf, n.unsigned_abs(), precision, true),
+            None => match fractions.to_i128() {
+                Some(n) => write_digits(buf, n.unsigned_abs(), precision, true),
+                None => write_digits(buf, GenericDigits::new(fractions.clone()), precision, true),
+            },
+        }
+    } else {
+        match fractions.to_u64() {
+            Some(n) => write_digits(buf, n, precision, false),
+            None => match fractions.to_u128() {
+                Some(n) => write_digits(buf, n, precision, false),
+                None => write_digits(buf, GenericDigits::new(fractions.clone()), precision, false),
+            },
         }
-        Some(fractions)
+    }?;
+    let written = &buf[start..];
+    Some(std::str::from_utf8(written).expect("formatted decimal should be ASCII"))
+}
+
+/// Call `f` with the decimal representation of `fractions`.
+///
+/// Uses a stack buffer when possible, and only allocates for very large values.
+fn with_fractions_str<T, R>(fractions: &T, precision: u8, f: impl FnOnce(&str) -> R) -> R
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    let mut buf = [0u8; INLINE_FORMAT_LEN];
+    if let Some(s) = write_fractions(&mut buf, fractions, precision) {
+        return f(s);
+    }
+    let mut len = INLINE_FORMAT_LEN * 2;
+    loop {
+        let mut buf = vec![0u8; len];
+        if let Some(s) = write_fractions(&mut buf, fractions, precision) {
+            return f(s);
+        }
+        len *= 2;
+    }
+}
+
+/// Source of decimal digits, from the least significant to the most significant.
+trait DigitSource {
+    /// Remove the least significant digit and return it (in `0..=9`).
+    fn pop_digit(&mut self) -> u8;
+
+    /// Returns `true` once all the non-zero digits were popped.
+    fn is_empty(&self) -> bool;
+}
+
+impl DigitSource for u64 {
+    fn pop_digit(&mut self) -> u8 {
+        let digit = (*self % 10) as u8;
+        *self /= 10;
+        digit
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl DigitSource for u128 {
+    fn pop_digit(&mut self) -> u8 {
+        let digit = (*self % 10) as u8;
+        *self /= 10;
+        digit
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == 0
+    }
+}
+
+/// Digits of an arbitrary integer, possibly negative.
+///
+/// The divisor is built once, as building values may be expensive for arbitrary precision
+/// integers such as [num::BigInt].
+struct GenericDigits<T> {
+    value: T,
+    ten: T,
+}
+
+impl<T: From<u8>> GenericDigits<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            ten: 10.into(),
+        }
+    }
+}
+
+impl<T: Integer + ToPrimitive + From<u8>> DigitSource for GenericDigits<T> {
+    fn pop_digit(&mut self) -> u8 {
+        let (q, r) = self.value.div_rem(&self.ten);
+        self.value = q;
+        // `r` is in `-9..=9`, with the sign of the value
+        let digit = r.to_i8().expect("digit should be in -9..=9");
+        digit.unsigned_abs()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.value.is_zero()
+    }
+}
+
+/// Write the digits at the end of `buf`, with exactly `precision` fractional digits.
+///
+/// Returns the start index of the written bytes, or `None` if the buffer is too small.
+fn write_digits<D: DigitSource>(
+    buf: &mut [u8],
+    mut digits: D,
+    precision: u8,
+    negative: bool,
+) -> Option<usize> {
+    let precision = usize::from(precision);
+    let mut start = buf.len();
+    let mut push = |byte: u8| -> Option<()> {
+        start = start.checked_sub(1)?;
+        buf[start] = byte;
+        Some(())
+    };
+    let mut written: usize = 0;
+    loop {
+        if written == precision && precision > 0 {
+            push(b'.')?;
+        }
+        push(b'0' + digits.pop_digit())?;
+        written += 1;
+        if written > precision && digits.is_empty() {
+            break;
+        }
+    }
+    if negative {
+        push(b'-')?;
+    }
+    Some(start)
+}
+
+/// Formats the value with exactly `PRECISION` fractional digits.
+///
+/// An explicit precision (`{:.2}`) sets the minimal number of fractional digits instead:
+/// trailing zeros are added or removed to match it, but significant digits are never
+/// dropped since this would require rounding. Use [FixedDecimalFormat::max_fractional_digits]
+/// to round with an explicit mode.
+impl<T, const PRECISION: u8> Display for FixedDecimal<T, PRECISION>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt_decimal(f, self.fractions(), PRECISION)
+    }
+}
+
+/// Shared `Display` implementation of [FixedDecimal] and [DynDecimal].
+fn fmt_decimal<T>(f: &mut Formatter<'_>, fractions: &T, precision: u8) -> std::fmt::Result
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    with_fractions_str(fractions, precision, |s| match f.precision() {
+        None => pad_decimal(f, s),
+        Some(digits) => {
+            let (int_part, frac_part) = match s.find('.') {
+                Some(pos) => (&s[..pos], &s[pos + 1..]),
+                None => (s, ""),
+            };
+            // Only remove zeros: the significant digits are kept even past the precision
+            let significant = frac_part.trim_end_matches('0').len();
+            let frac_part = &frac_part[..significant.max(digits.min(frac_part.len()))];
+            let mut out = String::with_capacity(int_part.len() + 1 + digits.max(frac_part.len()));
+            out.push_str(int_part);
+            if !frac_part.is_empty() || digits > 0 {
+                out.push('.');
+                out.push_str(frac_part);
+                for _ in frac_part.len()..digits {
+                    out.push('0');
+                }
+            }
+            pad_decimal(f, &out)
+        }
+    })
+}
+
+/// Write a formatted decimal, honoring the width, fill, alignment, sign and zero-padding
+/// flags like the `Display` implementations of the primitive integers.
+fn pad_decimal(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    match s.strip_prefix('-') {
+        Some(digits) => f.pad_integral(false, "", digits),
+        None => f.pad_integral(true, "", s),
+    }
+}
+
+/// Options controlling the output of [FixedDecimal::format_with].
+///
+/// The default options match the `Display` implementation: exactly `PRECISION` fractional
+/// digits, no grouping and a `.` decimal separator.
+///
+/// The formatted value honors the width, fill and alignment flags of [std::fmt], but not the
+/// precision: use [FixedDecimalFormat::max_fractional_digits] instead.
+///
+/// # Example
+///
+/// ```
+/// use txdemo::fixed;
+/// use txdemo::fixed_decimal::{FixedDecimal, FixedDecimalFormat, RoundingMode};
+///
+/// let format = FixedDecimalFormat::new()
+///     .thousands_separator(Some(','))
+///     .max_fractional_digits(2, RoundingMode::HalfEven);
+/// let value = fixed!(FixedDecimal<u64, 4>, "1234567.8951");
+/// let formatted = value.format_with(&format).unwrap();
+/// assert_eq!(format!("[{:>14}]", formatted), "[  1,234,567.90]");
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FixedDecimalFormat {
+    trim_trailing_zeros: bool,
+    min_fractional_digits: u8,
+    max_fractional_digits: Option<(u8, RoundingMode)>,
+    thousands_separator: Option<char>,
+    decimal_separator: char,
+}
+
+impl FixedDecimalFormat {
+    /// Create the default format options.
+    pub const fn new() -> Self {
+        Self {
+            trim_trailing_zeros: false,
+            min_fractional_digits: 0,
+            max_fractional_digits: None,
+            thousands_separator: None,
+            decimal_separator: '.',
+        }
+    }
+
+    /// Remove the trailing zeros of the fractional part (default: `false`).
+    ///
+    /// At least `min_fractional_digits` are kept. If all the fractional digits are removed,
+    /// the decimal separator is omitted.
+    pub fn trim_trailing_zeros(mut self, trim: bool) -> Self {
+        self.trim_trailing_zeros = trim;
+        self
+    }
+
+    /// Minimal number of fractional digits, padded with zeros if needed (default: `0`).
+    pub fn min_fractional_digits(mut self, digits: u8) -> Self {
+        self.min_fractional_digits = digits;
+        self
+    }
+
+    /// Maximal number of fractional digits (default: no limit).
+    ///
+    /// Values with more significant digits are rounded with the provided mode. Use
+    /// [RoundingMode::Exact] to fail instead of rounding.
+    pub fn max_fractional_digits(mut self, digits: u8, mode: RoundingMode) -> Self {
+        self.max_fractional_digits = Some((digits, mode));
+        self
+    }
+
+    /// Separator between groups of three digits in the integral part (default: `None`).
+    pub fn thousands_separator(mut self, separator: Option<char>) -> Self {
+        self.thousands_separator = separator;
+        self
+    }
+
+    /// Character separating the integral and fractional parts (default: `.`).
+    pub fn decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+        self
+    }
+
+    /// Format `fractions` with the provided precision.
+    fn format_fractions<T>(
+        &self,
+        fractions: &T,
+        precision: u8,
+    ) -> Result<FormattedDecimal, ArithmeticError>
+    where
+        T: Integer + CheckedAdd + CheckedSub + ToPrimitive + From<u8> + Clone,
+    {
+        let (fractions, precision) = match self.max_fractional_digits {
+            Some((max, mode)) if max < precision => {
+                (div_pow10(fractions.clone(), precision - max, mode)?, max)
+            }
+            _ => (fractions.clone(), precision),
+        };
+        let out = with_fractions_str(&fractions, precision, |s| {
+            let (sign, digits) = match s.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", s),
+            };
+            let (int_part, frac_part) = match digits.find('.') {
+                Some(pos) => (&digits[..pos], &digits[pos + 1..]),
+                None => (digits, ""),
+            };
+            let min = usize::from(self.min_fractional_digits);
+            let frac_part = if self.trim_trailing_zeros {
+                let trimmed = frac_part.trim_end_matches('0');
+                &frac_part[..trimmed.len().max(min.min(frac_part.len()))]
+            } else {
+                frac_part
+            };
+            let mut out = String::with_capacity(s.len() + int_part.len() / 3 + min);
+            out.push_str(sign);
+            for (i, digit) in int_part.chars().enumerate() {
+                if let Some(separator) = self.thousands_separator {
+                    if i > 0 && (int_part.len() - i) % 3 == 0 {
+                        out.push(separator);
+                    }
+                }
+                out.push(digit);
+            }
+            if !frac_part.is_empty() || min > 0 {
+                out.push(self.decimal_separator);
+                out.push_str(frac_part);
+                for _ in frac_part.len()..min {
+                    out.push('0');
+                }
+            }
+            out
+        });
+        Ok(FormattedDecimal(out))
+    }
+}
+
+impl Default for FixedDecimalFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A decimal value formatted with [FixedDecimalFormat].
+///
+/// Its `Display` implementation honors the width, fill and alignment flags. The precision flag
+/// is ignored since the number of fractional digits is set by the format options.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FormattedDecimal(String);
+
+impl FormattedDecimal {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for FormattedDecimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = match f.width() {
+            Some(width) => width,
+            None => return f.write_str(&self.0),
+        };
+        let len = self.0.chars().count();
+        if len >= width {
+            return f.write_str(&self.0);
+        }
+        let padding = width - len;
+        let (before, after) = match f.align() {
+            Some(std::fmt::Alignment::Left) => (0, padding),
+            Some(std::fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+            // Numbers are right-aligned by default
+            Some(std::fmt::Alignment::Right) | None => (padding, 0),
+        };
+        let fill = f.fill();
+        for _ in 0..before {
+            f.write_char(fill)?;
+        }
+        f.write_str(&self.0)?;
+        for _ in 0..after {
+            f.write_char(fill)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedSub + ToPrimitive + From<u8> + Clone,
+{
+    /// Format this value with custom options.
+    ///
+    /// Fails only if the value must be rounded and the rounding mode is
+    /// [RoundingMode::Exact]. See [FixedDecimalFormat].
+    pub fn format_with(
+        &self,
+        format: &FixedDecimalFormat,
+    ) -> Result<FormattedDecimal, ArithmeticError> {
+        format.format_fractions(&self.0, PRECISION)
+    }
+}
+
+impl<T, const PRECISION: u8> Serialize for FixedDecimal<T, PRECISION>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.with_str(|s| serializer.serialize_str(s))
+    }
+}
+
+impl<'de, T, const PRECISION: u8> Deserialize<'de> for FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + FromPrimitive + From<u8>,
+{
+    /// Deserialize a fixed decimal from its string representation.
+    ///
+    /// See [FixedDecimal::deserialize_str]. Use [FixedDecimal::deserialize_number] or
+    /// [FixedDecimal::deserialize_number_or_float] with `#[serde(deserialize_with = "...")]`
+    /// to also accept numbers (e.g. `{"amount": 12}` in JSON).
+    fn deserialize<D: ::serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        Self::deserialize_str(deserializer)
+    }
+}
+
+impl<'de, T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + FromPrimitive + From<u8>,
+{
+    /// Deserialize a fixed decimal from either a string or an exact integer.
+    ///
+    /// Floats are rejected: see [FixedDecimal::deserialize_number_or_float] to opt in. This
+    /// relies on the format describing its own types (e.g. JSON or MessagePack): formats
+    /// inferring the type from the text, such as CSV, report decimal values as floats.
+    pub fn deserialize_number<D: ::serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(SerdeVisitor::new(false))
+    }
+
+    /// Deserialize a fixed decimal from its string representation.
+    ///
+    /// This is the same as the `Deserialize` implementation. It requests a string from the
+    /// deserializer, so it works with formats inferring the type from the text (such as CSV)
+    /// and with formats which don't describe their types (such as bincode). Exact integers
+    /// are still accepted if the format provides them regardless of the `str` hint (e.g.
+    /// MessagePack).
+    pub fn deserialize_str<D: ::serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        deserializer.deserialize_str(SerdeVisitor::new(false))
+    }
+
+    /// Deserialize a fixed decimal from either a string, an exact integer or a float.
+    ///
+    /// Floats are only accepted if their shortest decimal representation has at most
+    /// `PRECISION` fractional digits (e.g. `1.25` but not `0.1 + 0.2`).
+    ///
+    /// Note that the float is already rounded by the deserializer before this check: large
+    /// values with many digits (more than ~15 significant digits) may be accepted with a
+    /// different value than the input text. Prefer strings or integers when possible.
+    pub fn deserialize_number_or_float<D: ::serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> ::std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(SerdeVisitor::new(true))
+    }
+}
+
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedMul + From<u8>,
+{
+    /// Build a fixed decimal value from an integral number of units.
+    ///
+    /// Returns `None` if the value cannot be represented.
+    pub fn checked_from_int(units: T) -> Option<Self> {
+        if units.is_zero() {
+            return Some(Self::from_fractions(units));
+        }
+        let fractions = units.checked_mul(&Self::fractions_per_unit()?)?;
+        Some(Self::from_fractions(fractions))
+    }
+}
+
+/// Serde visitor for fixed decimal values.
+struct SerdeVisitor<T, const PRECISION: u8> {
+    /// Accept floats if their shortest representation is exact.
+    allow_float: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<T, const PRECISION: u8> SerdeVisitor<T, PRECISION> {
+    fn new(allow_float: bool) -> Self {
+        Self {
+            allow_float,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, T, const PRECISION: u8> ::serde::de::Visitor<'de> for SerdeVisitor<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + FromPrimitive + From<u8>,
+{
+    type Value = FixedDecimal<T, PRECISION>;
+
+    fn expecting(&self, fmt: &mut ::std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "a fixed-point decimal number with up to {} decimal digits",
+            PRECISION
+        )
+    }
+
+    fn visit_str<E: ::serde::de::Error>(
+        self,
+        value: &str,
+    ) -> ::std::result::Result<Self::Value, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: ::serde::de::Error>(self, value: u64) -> ::std::result::Result<Self::Value, E> {
+        T::from_u64(value)
+            .and_then(FixedDecimal::checked_from_int)
+            .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: ::serde::de::Error>(self, value: i64) -> ::std::result::Result<Self::Value, E> {
+        T::from_i64(value)
+            .and_then(FixedDecimal::checked_from_int)
+            .ok_or_else(|| E::invalid_value(Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u128<E: ::serde::de::Error>(
+        self,
+        value: u128,
+    ) -> ::std::result::Result<Self::Value, E> {
+        T::from_u128(value)
+            .and_then(FixedDecimal::checked_from_int)
+            .ok_or_else(|| E::invalid_value(Unexpected::Other("u128"), &self))
+    }
+
+    fn visit_i128<E: ::serde::de::Error>(
+        self,
+        value: i128,
+    ) -> ::std::result::Result<Self::Value, E> {
+        T::from_i128(value)
+            .and_then(FixedDecimal::checked_from_int)
+            .ok_or_else(|| E::invalid_value(Unexpected::Other("i128"), &self))
+    }
+
+    fn visit_f64<E: ::serde::de::Error>(self, value: f64) -> ::std::result::Result<Self::Value, E> {
+        if !self.allow_float || !value.is_finite() {
+            return Err(E::invalid_type(Unexpected::Float(value), &self));
+        }
+        if value == 0.0 {
+            // Avoid `-0`, rejected for unsigned types
+            return Ok(FixedDecimal::from_fractions(T::zero()));
+        }
+        // The `Display` implementation of `f64` uses the shortest representation which
+        // round-trips, and never uses the exponent notation.
+        value
+            .to_string()
+            .parse()
+            .map_err(|_| E::invalid_value(Unexpected::Float(value), &self))
+    }
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum ParseFixedDecimalError {
+    #[error("the magnitude of the number is too large")]
+    TooLarge,
+    #[error("the number of fractional digits in the input exceeds the supported precision ({})", .0)]
+    TooMuchFractionalDigits(u8),
+    #[error("invalid character at the byte {:?}", .0)]
+    InvalidChar(usize),
+    #[error("there are no digits in the input")]
+    NoDigits,
+}
+
+/// Options controlling the syntax accepted by [FixedDecimal::parse_with].
+///
+/// The default options match the strict syntax of the `FromStr` implementation:
+/// an optional `-` sign (for signed types), followed by digits with an optional
+/// `.` decimal separator.
+///
+/// # Example
+///
+/// ```
+/// use txdemo::fixed_decimal::{FixedDecimal, ParseOptions};
+///
+/// let options = ParseOptions::new()
+///     .thousands_separator(Some('.'))
+///     .decimal_separator(',');
+/// let actual = FixedDecimal::<u64, 4>::parse_with("1.000,25", &options);
+/// assert_eq!(actual, Ok(FixedDecimal::from_fractions(10002500)));
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ParseOptions {
+    allow_leading_plus: bool,
+    allow_exponent: bool,
+    thousands_separator: Option<char>,
+    decimal_separator: char,
+    trim_whitespace: bool,
+}
+
+impl ParseOptions {
+    /// Create the default (strict) parse options.
+    pub const fn new() -> Self {
+        Self {
+            allow_leading_plus: false,
+            allow_exponent: false,
+            thousands_separator: None,
+            decimal_separator: '.',
+            trim_whitespace: false,
+        }
+    }
+
+    /// Accept an explicit `+` sign before the number (default: `false`).
+    pub fn allow_leading_plus(mut self, allow: bool) -> Self {
+        self.allow_leading_plus = allow;
+        self
     }
-}
 
-impl<T: Integer + Debug, const PRECISION: u8> Debug for FixedDecimal<T, PRECISION> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "FixedDecimal({:?}e-{})", self.fractions(), PRECISION)
+    /// Accept a trailing exponent such as `1.5e3` (default: `false`).
+    ///
+    /// The value must still be exact: after applying the exponent, the number of
+    /// fractional digits must not exceed the precision of the target type.
+    pub fn allow_exponent(mut self, allow: bool) -> Self {
+        self.allow_exponent = allow;
+        self
     }
-}
 
-impl<T: Integer + CheckedAdd, const PRECISION: u8> FixedDecimal<T, PRECISION> {
-    pub fn checked_add(&self, v: &Self) -> Option<Self> {
-        num::CheckedAdd::checked_add(&self.0, &v.0).map(Self::from_fractions)
+    /// Accept a separator between the digits of the integral part, such as
+    /// `1,000` (default: `None`).
+    ///
+    /// The separator must be surrounded by digits. If it is the same as the decimal
+    /// separator, it is ignored.
+    pub fn thousands_separator(mut self, separator: Option<char>) -> Self {
+        self.thousands_separator = separator;
+        self
+    }
+
+    /// Character separating the integral and fractional parts (default: `.`).
+    pub fn decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+        self
+    }
+
+    /// Ignore leading and trailing whitespace (default: `false`).
+    pub fn trim_whitespace(mut self, trim: bool) -> Self {
+        self.trim_whitespace = trim;
+        self
     }
 }
 
-impl<T: Integer + CheckedSub, const PRECISION: u8> FixedDecimal<T, PRECISION> {
-    pub fn checked_sub(&self, v: &Self) -> Option<Self> {
-        num::CheckedSub::checked_sub(&self.0, &v.0).map(Self::from_fractions)
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl<T, const PRECISION: u8> Display for FixedDecimal<T, PRECISION>
+impl<T, const PRECISION: u8> FixedDecimal<T, PRECISION>
 where
-    T: Integer + Display + CheckedMul + CheckedSub + From<u8> + Clone,
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if PRECISION == 0 {
-            return self.0.fmt(f);
-        }
+    /// Parse a fixed decimal value with a custom syntax.
+    ///
+    /// See [ParseOptions] for the available options. Error positions are byte offsets in
+    /// the original input.
+    pub fn parse_with(s: &str, options: &ParseOptions) -> Result<Self, ParseFixedDecimalError> {
+        lex_with_options(s, options)
+    }
+}
 
-        let (sign, abs) = if self.0 < T::zero() {
-            ("-", T::zero().checked_sub(&self.0))
-        } else {
-            ("", Some(self.0.clone()))
-        };
+impl<T, const PRECISION: u8> FromStr for FixedDecimal<T, PRECISION>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
+{
+    type Err = ParseFixedDecimalError;
 
-        match abs {
-            Some(abs) => {
-                let (int, frac) = match Self::fractions_per_unit() {
-                    None => (T::zero(), abs),
-                    Some(fpu) => abs.div_rem(&fpu),
-                };
-
-                write!(
-                    f,
-                    "{}{}.{:0>precision$}",
-                    sign,
-                    int,
-                    frac,
-                    precision = usize::from(PRECISION)
-                )
-            }
-            None => {
-                // Failed to compute absolute value (e.g. -128i8)
-                // Fall back to manual printing...
-                let zero = T::zero();
-                let one = T::one();
-                let ten = T::from(10);
-                let mut n = self.0.clone();
-                // Digits from right to left
-                let mut digits: Vec<T> = Vec::new();
-                while n != zero {
-                    let (q, r) = n.div_mod_floor(&ten);
-                    let digit = if q < zero && r != zero {
-                        // Fix values after div_floor on negative dividend
-                        n = q.add(one.clone());
-                        ten.checked_sub(&r).expect("cannot represent digit")
-                    } else {
-                        n = q;
-                        r
-                    };
-                    digits.push(digit);
-                }
-                write!(f, "{}", sign)?;
-                let digit_count = usize::max(digits.len(), usize::from(PRECISION) + 1);
-                let separator_index = digit_count - usize::from(PRECISION);
-                for i in 0..digit_count {
-                    if i == separator_index {
-                        write!(f, ".")?;
-                    }
-                    let digit = digit_count
-                        .checked_sub(i + 1)
-                        .and_then(|idx| digits.get(idx));
-                    if let Some(d) = digit {
-                        write!(f, "{}", d)?;
-                    } else {
-                        write!(f, "0")?;
-                    }
-                }
-                Ok(())
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if is_u64::<T>() {
+            if let Some(fractions) =
+                parse_u64_fractions::<PRECISION>(s.as_bytes()).and_then(from_u64)
+            {
+                return Ok(Self::from_fractions(fractions));
             }
         }
+        lex_strict(s)
     }
 }
 
-impl<T, const PRECISION: u8> Serialize for FixedDecimal<T, PRECISION>
+/// Check if `T` is an unsigned 64-bit integer, the backing type of the `u64` fast path.
+fn is_u64<T: Integer + CheckedSub>() -> bool {
+    std::mem::size_of::<T>() == std::mem::size_of::<u64>() && T::neg_one().is_none()
+}
+
+/// Convert a `u64` to `T`, using only the bounds of the `FromStr` implementation.
+///
+/// This is only called when [is_u64] holds: the compiler reduces it to a no-op for `u64`.
+fn from_u64<T: Integer + CheckedAdd + CheckedMul + From<u8>>(value: u64) -> Option<T> {
+    let base = T::from(16).checked_mul(&T::from(16))?;
+    value
+        .to_be_bytes()
+        .iter()
+        .try_fold(T::zero(), |acc, &byte| {
+            acc.checked_mul(&base)?.checked_add(&T::from(byte))
+        })
+}
+
+/// Generic lexer for the strict syntax used by the `FromStr` implementation.
+fn lex_strict<T, const PRECISION: u8>(
+    s: &str,
+) -> Result<FixedDecimal<T, PRECISION>, ParseFixedDecimalError>
 where
-    T: Integer + Display + CheckedMul + CheckedSub + From<u8> + Clone,
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
 {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(self.to_string().as_str())
-    }
+    lex_strict_fractions(s, PRECISION).map(FixedDecimal::from_fractions)
 }
 
-impl<'de, T, const PRECISION: u8> Deserialize<'de> for FixedDecimal<T, PRECISION>
+/// Lex the strict syntax, returning the number of fractions for the provided precision.
+fn lex_strict_fractions<T>(s: &str, precision: u8) -> Result<T, ParseFixedDecimalError>
 where
-    T: Integer + CheckedAdd + CheckedMul + CheckedSub + Bounded + Display + From<u8>,
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
 {
-    fn deserialize<D: ::serde::Deserializer<'de>>(
-        deserializer: D,
-    ) -> ::std::result::Result<Self, D::Error> {
-        struct SerdeVisitor<T, const PRECISION: u8>(PhantomData<T>)
-        where
-            T: Integer + CheckedAdd + CheckedMul + CheckedSub + Bounded + Display + From<u8>;
-
-        impl<'de, T, const PRECISION: u8> ::serde::de::Visitor<'de> for SerdeVisitor<T, PRECISION>
-        where
-            T: Integer + CheckedAdd + CheckedMul + CheckedSub + Bounded + Display + From<u8>,
-        {
-            type Value = FixedDecimal<T, PRECISION>;
-
-            fn expecting(&self, fmt: &mut ::std::fmt::Formatter) -> std::fmt::Result {
-                write!(fmt, "a fixed-point decimal number with up to {p} decimal digits and in the range {}e-{p}..={}e-{p}", T::min_value(), T::max_value(), p=PRECISION)
-            }
-
-            fn visit_str<E: ::serde::de::Error>(
-                self,
-                value: &str,
-            ) -> ::std::result::Result<Self::Value, E> {
-                value.parse().map_err(E::custom)
-            }
+    let mut bytes = s.bytes().enumerate();
+    let mut sign = T::one();
+    if let Some(neg_one) = T::neg_one() {
+        let saved = bytes.clone();
+        match bytes.next() {
+            Some((_, b'-')) => sign = neg_one,
+            _ => bytes = saved,
         }
-
-        deserializer.deserialize_str(SerdeVisitor(PhantomData))
     }
+    lex_digits(bytes, sign, precision)
 }
 
-#[derive(Error, Debug, Eq, PartialEq)]
-pub enum ParseFixedDecimalError {
-    #[error("the magnitude of the number is too large")]
-    TooLarge,
-    #[error("the number of fractional digits in the input exceeds the supported precision ({})", .0)]
-    TooMuchFractionalDigits(u8),
-    #[error("invalid character at the byte {:?}", .0)]
-    InvalidChar(usize),
-    #[error("there are no digits in the input")]
-    NoDigits,
+/// Fast path to parse non-negative decimals in the strict syntax, using `u64` arithmetic.
+///
+/// Returns `None` if the input is not a well-formed non-negative number whose fractions fit in
+/// a `u64`: the caller must then fall back to the generic lexer, which also reports the
+/// precise error. This keeps the error semantics identical across both paths.
+fn parse_u64_fractions<const PRECISION: u8>(input: &[u8]) -> Option<u64> {
+    let (int_part, frac_part) = match input.iter().position(|&c| c == b'.') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => (input, &input[input.len()..]),
+    };
+    if (int_part.is_empty() && frac_part.is_empty()) || frac_part.len() > usize::from(PRECISION) {
+        return None;
+    }
+    let int_value = parse_u64_digits(int_part)?;
+    let frac_value = parse_u64_digits(frac_part)?;
+    let frac_scale = pow10::<u64>(PRECISION - frac_part.len() as u8)?;
+    int_value
+        .checked_mul(pow10::<u64>(PRECISION)?)?
+        .checked_add(frac_value * frac_scale)
 }
 
-impl<T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>, const PRECISION: u8> FromStr
-    for FixedDecimal<T, PRECISION>
-{
-    type Err = ParseFixedDecimalError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut bytes = s.bytes().enumerate();
-        let mut sign = T::one();
-        if let Some(neg_one) = T::neg_one() {
-            let saved = bytes.clone();
-            match bytes.next() {
-                Some((_, b'-')) => sign = neg_one,
-                _ => bytes = saved,
-            }
+/// Parse a sequence of up to 19 ASCII digits (so the result never overflows).
+///
+/// Returns `None` if the input is too long or contains a non-digit character.
+fn parse_u64_digits(digits: &[u8]) -> Option<u64> {
+    if digits.len() > 19 {
+        return None;
+    }
+    let mut chunks = digits.chunks_exact(8);
+    let mut value: u64 = 0;
+    for chunk in &mut chunks {
+        let chunk: [u8; 8] = chunk.try_into().expect("chunk should have exactly 8 bytes");
+        value = value * 100_000_000 + parse_8_digits(chunk)?;
+    }
+    for &c in chunks.remainder() {
+        if !c.is_ascii_digit() {
+            return None;
         }
-        lex_digits(bytes, sign)
+        value = value * 10 + u64::from(c - b'0');
+    }
+    Some(value)
+}
+
+/// Parse 8 ASCII digits at once, using SIMD within a register (SWAR).
+///
+/// See <https://lemire.me/blog/2022/01/21/swar-explained-parsing-eight-digits/>.
+fn parse_8_digits(chunk: [u8; 8]) -> Option<u64> {
+    const HIGH_NIBBLES: u64 = 0xf0f0_f0f0_f0f0_f0f0;
+    const ZEROS: u64 = 0x3030_3030_3030_3030;
+    // The first digit is stored in the least significant byte
+    let value = u64::from_le_bytes(chunk);
+    // Every byte must be in `0x30..=0x39`: the high nibble must be `3`, and must still be `3`
+    // after adding `6` to the byte.
+    let is_digits = (value & HIGH_NIBBLES) == ZEROS
+        && (value.wrapping_add(0x0606_0606_0606_0606) & HIGH_NIBBLES) == ZEROS;
+    if !is_digits {
+        return None;
     }
+    let value = value - ZEROS;
+    // Combine pairs of digits, then pairs of pairs, then the two halves
+    let value = value.wrapping_mul(10) + (value >> 8);
+    let value = ((value & 0x0000_00ff_0000_00ff).wrapping_mul(100 + (1_000_000 << 32))
+        + ((value >> 16) & 0x0000_00ff_0000_00ff).wrapping_mul(1 + (10_000 << 32)))
+        >> 32;
+    Some(value)
 }
 
 /// `signum`: -1 or 1
-fn lex_digits<T: Integer + CheckedAdd + CheckedMul + From<u8>, const PRECISION: u8>(
+fn lex_digits<T: Integer + CheckedAdd + CheckedMul + From<u8>>(
     mut input: std::iter::Enumerate<std::str::Bytes>,
     signum: T,
-) -> Result<FixedDecimal<T, PRECISION>, ParseFixedDecimalError> {
+    precision: u8,
+) -> Result<T, ParseFixedDecimalError> {
     let mut fractions: T = T::zero();
     let mut has_digit: bool = false;
     let mut decimal_digits: Option<u16> = None;
@@ -243,8 +1523,8 @@ fn lex_digits<T: Integer + CheckedAdd + CheckedMul + From<u8>, const PRECISION:
             Some((_, c @ b'0'..=b'9')) => {
                 has_digit = true;
                 if let Some(dd) = decimal_digits {
-                    if dd == u16::from(PRECISION) {
-                        return Err(ParseFixedDecimalError::TooMuchFractionalDigits(PRECISION));
+                    if dd == u16::from(precision) {
+                        return Err(ParseFixedDecimalError::TooMuchFractionalDigits(precision));
                     }
                     decimal_digits = Some(dd + 1);
                 }
@@ -273,15 +1553,582 @@ fn lex_digits<T: Integer + CheckedAdd + CheckedMul + From<u8>, const PRECISION:
     if !has_digit {
         return Err(ParseFixedDecimalError::NoDigits);
     }
-    // Shift the current value to match the expected precision
-    let mut decimal_digits = decimal_digits.unwrap_or(0);
-    while decimal_digits < u16::from(PRECISION) {
-        decimal_digits += 1;
-        fractions = fractions
-            .checked_mul(&ten)
-            .ok_or(ParseFixedDecimalError::TooLarge)?;
+    // Shift the current value to match the expected precision
+    let mut decimal_digits = decimal_digits.unwrap_or(0);
+    while decimal_digits < u16::from(precision) {
+        decimal_digits += 1;
+        fractions = fractions
+            .checked_mul(&ten)
+            .ok_or(ParseFixedDecimalError::TooLarge)?;
+    }
+    Ok(fractions)
+}
+
+/// Lex a decimal number using the syntax defined by `options`.
+///
+/// Contrary to [lex_digits], the digits are counted first and the value is only
+/// shifted to the expected precision once the exponent is known.
+fn lex_with_options<T, const PRECISION: u8>(
+    input: &str,
+    options: &ParseOptions,
+) -> Result<FixedDecimal<T, PRECISION>, ParseFixedDecimalError>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
+{
+    let (offset, input) = if options.trim_whitespace {
+        let trimmed = input.trim_start();
+        (input.len() - trimmed.len(), trimmed.trim_end())
+    } else {
+        (0, input)
+    };
+    let mut chars = input
+        .char_indices()
+        .map(|(pos, c)| (pos + offset, c))
+        .peekable();
+
+    let mut signum = T::one();
+    match chars.peek() {
+        Some((_, '-')) => {
+            if let Some(neg_one) = T::neg_one() {
+                signum = neg_one;
+                chars.next();
+            }
+        }
+        Some((_, '+')) if options.allow_leading_plus => {
+            chars.next();
+        }
+        _ => {}
+    }
+
+    let ten: T = 10.into();
+    let mut mantissa: T = T::zero();
+    let mut has_digit: bool = false;
+    let mut decimal_digits: Option<i64> = None;
+    // Position of the last thousands separator, if it was not followed by a digit yet
+    let mut pending_separator: Option<usize> = None;
+    let mut last_is_digit: bool = false;
+    let mut exponent: i64 = 0;
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '0'..='9' => {
+                has_digit = true;
+                last_is_digit = true;
+                pending_separator = None;
+                if let Some(dd) = decimal_digits.as_mut() {
+                    *dd += 1;
+                }
+                let digit: u8 = (c as u8) - b'0';
+                let digit: T = digit.into();
+                let digit = signum
+                    .checked_mul(&digit)
+                    .ok_or(ParseFixedDecimalError::TooLarge)?;
+                mantissa = mantissa
+                    .checked_mul(&ten)
+                    .ok_or(ParseFixedDecimalError::TooLarge)?
+                    .checked_add(&digit)
+                    .ok_or(ParseFixedDecimalError::TooLarge)?;
+            }
+            c if c == options.decimal_separator => {
+                if decimal_digits.is_some() || pending_separator.is_some() {
+                    return Err(ParseFixedDecimalError::InvalidChar(pos));
+                }
+                decimal_digits = Some(0);
+                last_is_digit = false;
+            }
+            c if Some(c) == options.thousands_separator => {
+                if decimal_digits.is_some() || !last_is_digit {
+                    return Err(ParseFixedDecimalError::InvalidChar(pos));
+                }
+                pending_separator = Some(pos);
+                last_is_digit = false;
+            }
+            'e' | 'E' if options.allow_exponent && has_digit && pending_separator.is_none() => {
+                // The exponent is always the last part of the input
+                exponent =
+                    lex_exponent(&mut chars)?.ok_or(ParseFixedDecimalError::InvalidChar(pos))?;
+            }
+            _ => return Err(ParseFixedDecimalError::InvalidChar(pos)),
+        }
+    }
+    if let Some(pos) = pending_separator {
+        return Err(ParseFixedDecimalError::InvalidChar(pos));
+    }
+    if !has_digit {
+        return Err(ParseFixedDecimalError::NoDigits);
+    }
+    let fractional_digits = decimal_digits.unwrap_or(0) - exponent;
+    if fractional_digits > i64::from(PRECISION) {
+        return Err(ParseFixedDecimalError::TooMuchFractionalDigits(PRECISION));
+    }
+    if mantissa.is_zero() {
+        return Ok(FixedDecimal::from_fractions(mantissa));
+    }
+    // Shift the current value to match the expected precision
+    for _ in fractional_digits..i64::from(PRECISION) {
+        mantissa = mantissa
+            .checked_mul(&ten)
+            .ok_or(ParseFixedDecimalError::TooLarge)?;
+    }
+    Ok(FixedDecimal::from_fractions(mantissa))
+}
+
+/// Lex the exponent following an `e` or `E` character, until the end of the input.
+///
+/// Returns `None` if there are no exponent digits.
+fn lex_exponent(
+    chars: &mut impl Iterator<Item = (usize, char)>,
+) -> Result<Option<i64>, ParseFixedDecimalError> {
+    /// Larger exponents are rejected to bound the time spent shifting the value.
+    const MAX_EXPONENT: i64 = u16::MAX as i64;
+
+    let mut chars = chars.peekable();
+    let signum: i64 = match chars.peek() {
+        Some((_, '-')) => {
+            chars.next();
+            -1
+        }
+        Some((_, '+')) => {
+            chars.next();
+            1
+        }
+        _ => 1,
+    };
+    let mut exponent: Option<i64> = None;
+    for (pos, c) in chars {
+        let digit = match c.to_digit(10) {
+            Some(digit) => i64::from(digit),
+            None => return Err(ParseFixedDecimalError::InvalidChar(pos)),
+        };
+        let mut value = exponent.unwrap_or(0) * 10 + digit;
+        if value > MAX_EXPONENT {
+            if signum > 0 {
+                return Err(ParseFixedDecimalError::TooLarge);
+            }
+            // Saturate: such a negative exponent always exceeds the supported precision.
+            value = MAX_EXPONENT + 1;
+        }
+        exponent = Some(value);
+    }
+    Ok(exponent.map(|e| signum * e))
+}
+
+/// Returns `10 ** exp`, or `None` if it cannot be represented by `T`.
+fn pow10<T: Integer + CheckedMul + From<u8>>(exp: u8) -> Option<T> {
+    let ten: T = 10.into();
+    let mut result = T::one();
+    for _ in 0..exp {
+        result = result.checked_mul(&ten)?;
+    }
+    Some(result)
+}
+
+/// Magnitude of the part discarded by a division, relative to half of the divisor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum Discarded {
+    Zero,
+    BelowHalf,
+    Half,
+    AboveHalf,
+}
+
+impl Discarded {
+    /// Classify the remainder `r` of a division by `d`.
+    ///
+    /// `r` must be the remainder of a truncated division (`|r| < |d|`).
+    fn from_remainder<T: Integer + CheckedSub + Clone>(r: &T, d: &T) -> Self {
+        let zero = T::zero();
+        if *r == zero {
+            return Self::Zero;
+        }
+        // Distance from the remainder to the divisor, with the sign of `d`:
+        // `|complement| = |d| - |r|`. Computing it this way avoids `|d|` which may not be
+        // representable (e.g. `i8::MIN`).
+        let complement = if (*r < zero) == (*d < zero) {
+            d.clone() - r.clone()
+        } else {
+            d.clone() + r.clone()
+        };
+        match abs(r.clone()).cmp(&abs(complement)) {
+            std::cmp::Ordering::Less => Self::BelowHalf,
+            std::cmp::Ordering::Equal => Self::Half,
+            std::cmp::Ordering::Greater => Self::AboveHalf,
+        }
+    }
+}
+
+/// Absolute value of `x`.
+///
+/// Must only be called with values whose magnitude is known to be representable.
+fn abs<T: Integer + CheckedSub>(x: T) -> T {
+    if x < T::zero() {
+        T::zero()
+            .checked_sub(&x)
+            .expect("absolute value should be representable")
+    } else {
+        x
+    }
+}
+
+/// Apply the rounding `mode` to the truncated quotient `q`.
+///
+/// `negative` is the sign of the exact (non-truncated) result.
+fn round_quotient<T: Integer + CheckedAdd + CheckedSub>(
+    q: T,
+    discarded: Discarded,
+    negative: bool,
+    mode: RoundingMode,
+) -> Result<T, ArithmeticError> {
+    if discarded == Discarded::Zero {
+        return Ok(q);
+    }
+    let away_from_zero = match mode {
+        RoundingMode::Exact => return Err(ArithmeticError::Inexact),
+        RoundingMode::TowardZero => false,
+        RoundingMode::Floor => negative,
+        RoundingMode::Ceiling => !negative,
+        RoundingMode::HalfUp => discarded >= Discarded::Half,
+        RoundingMode::HalfEven => {
+            discarded == Discarded::AboveHalf || (discarded == Discarded::Half && q.is_odd())
+        }
+    };
+    if !away_from_zero {
+        return Ok(q);
+    }
+    let adjusted = if negative {
+        q.checked_sub(&T::one())
+    } else {
+        q.checked_add(&T::one())
+    };
+    adjusted.ok_or(ArithmeticError::Overflow)
+}
+
+/// Compute `n / 10 ** exp`, using the provided rounding mode.
+fn div_pow10<T>(n: T, exp: u8, mode: RoundingMode) -> Result<T, ArithmeticError>
+where
+    T: Integer + CheckedAdd + CheckedSub + From<u8> + Clone,
+{
+    let zero = T::zero();
+    let ten: T = 10.into();
+    let five: T = 5.into();
+    let negative = n < zero;
+    let mut q = n;
+    // Most significant discarded digit
+    let mut top = T::zero();
+    // Whether any of the less significant discarded digits is non-zero
+    let mut sticky = false;
+    for _ in 0..exp {
+        let (next_q, digit) = q.div_rem(&ten);
+        sticky = sticky || top != zero;
+        top = digit;
+        q = next_q;
+    }
+    let top = abs(top);
+    let discarded = if top == zero && !sticky {
+        Discarded::Zero
+    } else if top < five {
+        Discarded::BelowHalf
+    } else if top == five && !sticky {
+        Discarded::Half
+    } else {
+        Discarded::AboveHalf
+    };
+    round_quotient(q, discarded, negative, mode)
+}
+
+/// Compute `a * b / 10 ** exp`, using the provided rounding mode.
+fn mul_unshifted<T>(a: &T, b: &T, exp: u8, mode: RoundingMode) -> Result<T, ArithmeticError>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + From<u8> + Clone,
+{
+    let d: T = match pow10(exp) {
+        Some(d) => d,
+        None => {
+            // `10 ** exp` is not representable: compute the full product directly.
+            let n = a.checked_mul(b).ok_or(ArithmeticError::Overflow)?;
+            return div_pow10(n, exp, mode);
+        }
+    };
+    let negative = (*a < T::zero()) != (*b < T::zero());
+    // With `a = aq * d + ar` and `b = bq * d + br`:
+    // `a * b / d = a * bq + aq * br + ar * br / d`
+    // All the terms have the same sign, and only the last one needs rounding.
+    let (aq, ar) = a.div_rem(&d);
+    let (bq, br) = b.div_rem(&d);
+    let (q, r) = ar
+        .checked_mul(&br)
+        .ok_or(ArithmeticError::Overflow)?
+        .div_rem(&d);
+    let q = a
+        .checked_mul(&bq)
+        .and_then(|x| x.checked_add(&aq.checked_mul(&br)?))
+        .and_then(|x| x.checked_add(&q))
+        .ok_or(ArithmeticError::Overflow)?;
+    round_quotient(q, Discarded::from_remainder(&r, &d), negative, mode)
+}
+
+/// Compute `a * 10 ** exp / b`, using the provided rounding mode.
+///
+/// This is computed as a long division so `a * 10 ** exp` does not need to be representable.
+fn div_shifted<T>(a: &T, b: &T, exp: u8, mode: RoundingMode) -> Result<T, ArithmeticError>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv + From<u8> + Clone,
+{
+    if b.is_zero() {
+        return Err(ArithmeticError::DivisionByZero);
+    }
+    let ten: T = 10.into();
+    let negative = (*a < T::zero()) != (*b < T::zero());
+    // Only fails for `MIN / -1`
+    a.checked_div(b).ok_or(ArithmeticError::Overflow)?;
+    let (mut q, mut r) = a.div_rem(b);
+    for _ in 0..exp {
+        let (digit, next_r) = mul10_div_rem(&r, b);
+        q = q
+            .checked_mul(&ten)
+            .and_then(|q| q.checked_add(&digit))
+            .ok_or(ArithmeticError::Overflow)?;
+        r = next_r;
+    }
+    round_quotient(q, Discarded::from_remainder(&r, b), negative, mode)
+}
+
+/// Compute `(10 * r / b, 10 * r % b)` (truncated division), with `|r| < |b|`.
+///
+/// If `10 * r` is not representable (large divisors), the quotient digit is
+/// computed by adding `r` ten times and removing `b` whenever the accumulator
+/// reaches it, so all the intermediate values stay below `|b|`.
+fn mul10_div_rem<T>(r: &T, b: &T) -> (T, T)
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + From<u8> + Clone,
+{
+    let ten: T = 10.into();
+    if let Some(n) = r.checked_mul(&ten) {
+        return n.div_rem(b);
+    }
+    let zero = T::zero();
+    let same_sign = (*r < zero) == (*b < zero);
+    // `|complement| = |b| - |r|`, with the sign of `b` (see `Discarded::from_remainder`)
+    let complement = if same_sign {
+        b.clone() - r.clone()
+    } else {
+        b.clone() + r.clone()
+    };
+    let complement_abs = abs(complement.clone());
+    let mut digit = T::zero();
+    // Always has the sign of `r` (or is zero)
+    let mut acc = T::zero();
+    for _ in 0..10 {
+        if abs(acc.clone()) >= complement_abs {
+            // `|acc + r| >= |b|`: remove `b` from the accumulator
+            acc = if (acc < zero) == (complement < zero) {
+                acc - complement.clone()
+            } else {
+                acc + complement.clone()
+            };
+            digit = if same_sign {
+                digit + T::one()
+            } else {
+                digit - T::one()
+            };
+        } else {
+            acc = acc + r.clone();
+        }
+    }
+    (digit, acc)
+}
+
+/// A decimal number whose precision is only known at runtime, backed by a `T`.
+///
+/// This is the runtime counterpart of [FixedDecimal], intended for ledgers handling
+/// assets with different minor units (e.g. `0` for JPY, `2` for USD, `8` for BTC). It
+/// provides the same guarantees: arithmetic is checked and rounding always requires an
+/// explicit [RoundingMode].
+///
+/// Values with different precisions are never mixed implicitly: combining them fails with
+/// [DynDecimalError::PrecisionMismatch], use [DynDecimal::rescale] first.
+///
+/// Equality compares both the fractions and the precision: `1.0` and `1.00` are different.
+///
+/// # Example
+///
+/// ```
+/// use txdemo::fixed;
+/// use txdemo::fixed_decimal::{DynDecimal, FixedDecimal};
+///
+/// let usd = DynDecimal::from(fixed!(FixedDecimal<u64, 2>, "1.25"));
+/// let btc = DynDecimal::parse("0.00000001", 8).unwrap();
+/// assert!(usd.checked_add(&btc).is_err());
+/// let usd = usd.rescale(8).unwrap();
+/// assert_eq!(usd.checked_add(&btc).unwrap().to_string(), "1.25000001");
+/// ```
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct DynDecimal<T: Integer> {
+    fractions: T,
+    precision: u8,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DynDecimalError {
+    #[error("the operands have different precisions: {} and {}", .left, .right)]
+    PrecisionMismatch { left: u8, right: u8 },
+    #[error("the magnitude of the result is too large")]
+    Overflow,
+}
+
+impl<T: Integer, const PRECISION: u8> From<FixedDecimal<T, PRECISION>> for DynDecimal<T> {
+    fn from(value: FixedDecimal<T, PRECISION>) -> Self {
+        Self::from_fractions(value.0, PRECISION)
+    }
+}
+
+impl<T: Integer, const PRECISION: u8> TryFrom<DynDecimal<T>> for FixedDecimal<T, PRECISION> {
+    type Error = DynDecimalError;
+
+    /// Convert a runtime-precision value with exactly the precision `PRECISION`.
+    ///
+    /// Use [DynDecimal::rescale] first to convert from a different precision.
+    fn try_from(value: DynDecimal<T>) -> Result<Self, Self::Error> {
+        if value.precision != PRECISION {
+            return Err(DynDecimalError::PrecisionMismatch {
+                left: value.precision,
+                right: PRECISION,
+            });
+        }
+        Ok(Self::from_fractions(value.fractions))
+    }
+}
+
+impl<T: Integer> DynDecimal<T> {
+    /// Build a decimal value from a number of fractions (`10 ** (-precision)`).
+    pub fn from_fractions(fractions: T, precision: u8) -> Self {
+        Self {
+            fractions,
+            precision,
+        }
+    }
+
+    pub fn fractions(&self) -> &T {
+        &self.fractions
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Check that `self` and `other` have the same precision.
+    fn same_precision(&self, other: &Self) -> Result<u8, DynDecimalError> {
+        if self.precision == other.precision {
+            Ok(self.precision)
+        } else {
+            Err(DynDecimalError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            })
+        }
+    }
+}
+
+impl<T: Integer + CheckedAdd> DynDecimal<T> {
+    /// Add two values with the same precision.
+    pub fn checked_add(&self, v: &Self) -> Result<Self, DynDecimalError> {
+        let precision = self.same_precision(v)?;
+        num::CheckedAdd::checked_add(&self.fractions, &v.fractions)
+            .map(|fractions| Self::from_fractions(fractions, precision))
+            .ok_or(DynDecimalError::Overflow)
+    }
+}
+
+impl<T: Integer + CheckedSub> DynDecimal<T> {
+    /// Subtract two values with the same precision.
+    pub fn checked_sub(&self, v: &Self) -> Result<Self, DynDecimalError> {
+        let precision = self.same_precision(v)?;
+        num::CheckedSub::checked_sub(&self.fractions, &v.fractions)
+            .map(|fractions| Self::from_fractions(fractions, precision))
+            .ok_or(DynDecimalError::Overflow)
+    }
+}
+
+impl<T> DynDecimal<T>
+where
+    T: Integer + CheckedAdd + CheckedSub + CheckedMul + From<u8> + Clone,
+{
+    /// Convert this value to another precision, without any precision loss.
+    ///
+    /// See [FixedDecimal::rescale].
+    pub fn rescale(&self, new_precision: u8) -> Result<Self, ArithmeticError> {
+        self.rescale_with(new_precision, RoundingMode::Exact)
+    }
+
+    /// Convert this value to another precision, using the provided rounding
+    /// mode if the precision is narrowed.
+    pub fn rescale_with(
+        &self,
+        new_precision: u8,
+        mode: RoundingMode,
+    ) -> Result<Self, ArithmeticError> {
+        rescale_fractions(&self.fractions, self.precision, new_precision, mode)
+            .map(|fractions| Self::from_fractions(fractions, new_precision))
+    }
+
+    /// Convert this value to a [FixedDecimal], rescaling it without any precision loss.
+    ///
+    /// Returns `None` if the value cannot be represented with the precision `PRECISION`.
+    pub fn to_fixed<const PRECISION: u8>(&self) -> Option<FixedDecimal<T, PRECISION>> {
+        self.rescale(PRECISION)
+            .ok()
+            .map(|value| FixedDecimal::from_fractions(value.fractions))
+    }
+}
+
+impl<T> DynDecimal<T>
+where
+    T: Integer + CheckedAdd + CheckedMul + CheckedSub + From<u8>,
+{
+    /// Parse a decimal value with the strict syntax of [FixedDecimal::from_str], using the
+    /// provided precision.
+    pub fn parse(s: &str, precision: u8) -> Result<Self, ParseFixedDecimalError> {
+        lex_strict_fractions(s, precision)
+            .map(|fractions| Self::from_fractions(fractions, precision))
+    }
+}
+
+impl<T> DynDecimal<T>
+where
+    T: Integer + CheckedAdd + CheckedSub + ToPrimitive + From<u8> + Clone,
+{
+    /// Format this value with custom options.
+    ///
+    /// See [FixedDecimal::format_with].
+    pub fn format_with(
+        &self,
+        format: &FixedDecimalFormat,
+    ) -> Result<FormattedDecimal, ArithmeticError> {
+        format.format_fractions(&self.fractions, self.precision)
+    }
+}
+
+/// Formats the value with its own precision, or with an explicit precision like [FixedDecimal].
+impl<T> Display for DynDecimal<T>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt_decimal(f, &self.fractions, self.precision)
+    }
+}
+
+impl<T> Serialize for DynDecimal<T>
+where
+    T: Integer + ToPrimitive + From<u8> + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        with_fractions_str(&self.fractions, self.precision, |s| {
+            serializer.serialize_str(s)
+        })
     }
-    Ok(FixedDecimal::from_fractions(fractions))
 }
 
 /// Helper trait to detect if an integer type is signed or not by building `-1`
@@ -300,7 +2147,14 @@ impl<T: Integer + CheckedSub> NegOne for T {
 
 #[cfg(test)]
 mod test {
-    use crate::fixed_decimal::{FixedDecimal, ParseFixedDecimalError};
+    use crate::fixed_decimal::{
+        from_u64, is_u64, lex_strict, parse_8_digits, ArithmeticError, CheckedIteratorExt,
+        DynDecimal, DynDecimalError, FixedDecimal, FixedDecimalFormat, FromF64Error,
+        ParseFixedDecimalError, ParseOptions, RoundingMode,
+    };
+    use serde::de::IntoDeserializer;
+    use serde::Deserialize;
+    use std::convert::TryFrom;
     use std::str::FromStr;
 
     macro_rules! test_parse_i16_4 {
@@ -451,4 +2305,988 @@ mod test {
         display_i16_4_neg3_2767("-3.2767", "-3.2767");
         // display_i16_4_neg3_2768("-3.2768", "-3.2768");
     }
+
+    macro_rules! test_checked_mul {
+        ($($name:ident($typ:ty, $precision:literal, $left:literal, $rhs_precision:literal, $right:literal, $mode:ident, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let left = FixedDecimal::<$typ, $precision>::from_str($left).unwrap();
+                    let right = FixedDecimal::<$typ, $rhs_precision>::from_str($right).unwrap();
+                    let actual = left.checked_mul(&right, RoundingMode::$mode).map(|r| r.to_string());
+                    let expected: Result<&str, ArithmeticError> = $expected;
+                    assert_eq!(actual.as_deref().map_err(|e| *e), expected);
+                }
+            )+
+        };
+    }
+
+    test_checked_mul! {
+        mul_u64_4_exact(u64, 4, "12.5000", 2, "1.20", Exact, Ok("15.0000"));
+        mul_u64_4_inexact(u64, 4, "0.0001", 2, "0.50", Exact, Err(ArithmeticError::Inexact));
+        mul_u64_4_half_even_down(u64, 4, "0.0001", 2, "0.50", HalfEven, Ok("0.0000"));
+        mul_u64_4_half_even_up(u64, 4, "0.0003", 2, "0.50", HalfEven, Ok("0.0002"));
+        mul_u64_4_half_up(u64, 4, "0.0001", 2, "0.50", HalfUp, Ok("0.0001"));
+        mul_u64_4_toward_zero(u64, 4, "0.0001", 2, "0.99", TowardZero, Ok("0.0000"));
+        mul_u64_4_floor(u64, 4, "0.0001", 2, "0.99", Floor, Ok("0.0000"));
+        mul_u64_4_ceiling(u64, 4, "0.0001", 2, "0.01", Ceiling, Ok("0.0001"));
+        mul_u64_4_large(u64, 4, "1000000000000000.0000", 4, "1.0500", Exact, Ok("1050000000000000.0000"));
+        mul_u64_4_overflow(u64, 4, "1000000000000000.0000", 0, "2", Exact, Err(ArithmeticError::Overflow));
+        mul_i64_4_neg_half_up(i64, 4, "-0.0001", 1, "0.5", HalfUp, Ok("-0.0001"));
+        mul_i64_4_neg_half_even(i64, 4, "-0.0001", 1, "0.5", HalfEven, Ok("0.0000"));
+        mul_i64_4_neg_floor(i64, 4, "-0.0001", 1, "0.1", Floor, Ok("-0.0001"));
+        mul_i64_4_neg_ceiling(i64, 4, "-0.0001", 1, "0.1", Ceiling, Ok("0.0000"));
+        mul_i64_4_neg_neg(i64, 4, "-1.5000", 2, "-2.00", Exact, Ok("3.0000"));
+        mul_i16_4_unrepresentable_unit(i16, 4, "1.0000", 5, "0.00003", Ceiling, Ok("0.0001"));
+    }
+
+    macro_rules! test_checked_div {
+        ($($name:ident($typ:ty, $precision:literal, $left:literal, $rhs_precision:literal, $right:literal, $mode:ident, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let left = FixedDecimal::<$typ, $precision>::from_str($left).unwrap();
+                    let right = FixedDecimal::<$typ, $rhs_precision>::from_str($right).unwrap();
+                    let actual = left.checked_div(&right, RoundingMode::$mode).map(|r| r.to_string());
+                    let expected: Result<&str, ArithmeticError> = $expected;
+                    assert_eq!(actual.as_deref().map_err(|e| *e), expected);
+                }
+            )+
+        };
+    }
+
+    test_checked_div! {
+        div_u64_4_exact(u64, 4, "15.0000", 2, "1.20", Exact, Ok("12.5000"));
+        div_u64_4_inexact(u64, 4, "1.0000", 0, "3", Exact, Err(ArithmeticError::Inexact));
+        div_u64_4_third_half_even(u64, 4, "1.0000", 0, "3", HalfEven, Ok("0.3333"));
+        div_u64_4_two_thirds_half_even(u64, 4, "2.0000", 0, "3", HalfEven, Ok("0.6667"));
+        div_u64_4_two_thirds_toward_zero(u64, 4, "2.0000", 0, "3", TowardZero, Ok("0.6666"));
+        div_u64_4_tie_half_even(u64, 4, "0.0005", 1, "1.0", HalfEven, Ok("0.0005"));
+        div_u64_4_tie_half_even_down(u64, 4, "0.0001", 0, "2", HalfEven, Ok("0.0000"));
+        div_u64_4_tie_half_up(u64, 4, "0.0001", 0, "2", HalfUp, Ok("0.0001"));
+        div_u64_4_by_zero(u64, 4, "1.0000", 2, "0.00", HalfEven, Err(ArithmeticError::DivisionByZero));
+        div_u64_4_overflow(u64, 4, "1000000000000000.0000", 2, "0.01", Exact, Err(ArithmeticError::Overflow));
+        div_i64_4_neg_floor(i64, 4, "-1.0000", 0, "3", Floor, Ok("-0.3334"));
+        div_i64_4_neg_ceiling(i64, 4, "-1.0000", 0, "3", Ceiling, Ok("-0.3333"));
+        div_i64_4_neg_neg(i64, 4, "-1.0000", 0, "-4", Exact, Ok("0.2500"));
+        div_i16_0_min_neg_one(i16, 0, "-32768", 0, "-1", Exact, Err(ArithmeticError::Overflow));
+        div_i16_0_min_half_even(i16, 0, "-32768", 0, "3", HalfEven, Ok("-10923"));
+        div_u16_4_max_divisor(u16, 4, "6.5534", 4, "6.5535", HalfEven, Ok("1.0000"));
+        div_u16_4_max_divisor_toward_zero(u16, 4, "6.5534", 4, "6.5535", TowardZero, Ok("0.9999"));
+        div_u16_4_max_divisor_exact(u16, 4, "6.5535", 4, "6.5535", Exact, Ok("1.0000"));
+        div_u16_2_max_divisor_small_dividend(u16, 2, "0.01", 2, "655.35", Ceiling, Ok("0.01"));
+        div_i16_2_min_divisor(i16, 2, "-327.67", 2, "-327.68", TowardZero, Ok("0.99"));
+        div_i16_2_min_divisor_half_even(i16, 2, "-327.67", 2, "-327.68", HalfEven, Ok("1.00"));
+        div_i16_2_max_divisor_neg_floor(i16, 2, "327.67", 2, "-327.68", Floor, Ok("-1.00"));
+        div_i16_2_max_divisor_neg_ceiling(i16, 2, "-327.66", 2, "327.67", Ceiling, Ok("-0.99"));
+        div_i16_2_max_divisor_neg_exact(i16, 2, "-327.67", 2, "327.67", Exact, Ok("-1.00"));
+    }
+
+    #[test]
+    fn mul_int_u64_4() {
+        let actual = FixedDecimal::<u64, 4>::from_str("1.2345")
+            .unwrap()
+            .checked_mul_int(&3);
+        assert_eq!(actual, Some(FixedDecimal::from_fractions(37035)));
+    }
+
+    #[test]
+    fn div_int_u64_4() {
+        let actual = FixedDecimal::<u64, 4>::from_str("10.0000")
+            .unwrap()
+            .checked_div_int(&4, RoundingMode::Exact);
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(25000)));
+    }
+
+    macro_rules! test_rescale {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal, $new_precision:literal, $mode:ident, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input = FixedDecimal::<$typ, $precision>::from_str($input).unwrap();
+                    let actual = input.rescale_with::<$new_precision>(RoundingMode::$mode).map(|r| r.to_string());
+                    let expected: Result<&str, ArithmeticError> = $expected;
+                    assert_eq!(actual.as_deref().map_err(|e| *e), expected);
+                }
+            )+
+        };
+    }
+
+    test_rescale! {
+        rescale_u64_2_to_8(u64, 2, "12.34", 8, Exact, Ok("12.34000000"));
+        rescale_u64_4_to_4(u64, 4, "12.3456", 4, Exact, Ok("12.3456"));
+        rescale_u64_8_to_2_exact(u64, 8, "12.34000000", 2, Exact, Ok("12.34"));
+        rescale_u64_8_to_2_inexact(u64, 8, "12.34000001", 2, Exact, Err(ArithmeticError::Inexact));
+        rescale_u64_8_to_2_half_even(u64, 8, "12.34500000", 2, HalfEven, Ok("12.34"));
+        rescale_u64_8_to_2_half_even_sticky(u64, 8, "12.34500001", 2, HalfEven, Ok("12.35"));
+        rescale_u64_8_to_2_half_up(u64, 8, "12.34500000", 2, HalfUp, Ok("12.35"));
+        rescale_u64_8_to_2_toward_zero(u64, 8, "12.34999999", 2, TowardZero, Ok("12.34"));
+        rescale_i64_8_to_2_floor(i64, 8, "-12.34000001", 2, Floor, Ok("-12.35"));
+        rescale_i64_8_to_2_ceiling(i64, 8, "-12.34999999", 2, Ceiling, Ok("-12.34"));
+        rescale_u16_2_to_4_overflow(u16, 2, "655.35", 4, Exact, Err(ArithmeticError::Overflow));
+    }
+
+    #[test]
+    fn rescale_lossless() {
+        let input = FixedDecimal::<u64, 4>::from_str("1.5").unwrap();
+        assert_eq!(
+            input.rescale::<8>(),
+            Ok(FixedDecimal::from_fractions(150000000))
+        );
+        assert_eq!(input.rescale::<1>(), Ok(FixedDecimal::from_fractions(15)));
+        assert_eq!(input.rescale::<0>(), Err(ArithmeticError::Inexact));
+        let input = FixedDecimal::<u16, 2>::from_fractions(u16::MAX);
+        assert_eq!(input.rescale::<4>(), Err(ArithmeticError::Overflow));
+    }
+
+    #[test]
+    fn cast_u64_to_u128() {
+        let input = FixedDecimal::<u64, 4>::from_fractions(u64::MAX);
+        let actual: FixedDecimal<u128, 4> = input.cast();
+        assert_eq!(actual, FixedDecimal::from_fractions(u128::from(u64::MAX)));
+    }
+
+    #[test]
+    fn checked_cast_u64_to_i64() {
+        let input = FixedDecimal::<u64, 4>::from_fractions(12345);
+        assert_eq!(
+            input.checked_cast::<i64>(),
+            Some(FixedDecimal::from_fractions(12345))
+        );
+        let input = FixedDecimal::<u64, 4>::from_fractions(u64::MAX);
+        assert_eq!(input.checked_cast::<i64>(), None);
+    }
+
+    fn lenient_options() -> ParseOptions {
+        ParseOptions::new()
+            .allow_leading_plus(true)
+            .allow_exponent(true)
+            .thousands_separator(Some(','))
+            .trim_whitespace(true)
+    }
+
+    macro_rules! test_parse_with {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal, $options:expr, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let actual = FixedDecimal::<$typ, $precision>::parse_with($input, &$options);
+                    assert_eq!(actual, $expected);
+                }
+            )+
+        };
+    }
+
+    test_parse_with! {
+        parse_with_default_plain(u64, 4, "1.25", ParseOptions::new(), Ok(FixedDecimal::from_fractions(12500)));
+        parse_with_default_plus(u64, 4, "+1.25", ParseOptions::new(), Err(ParseFixedDecimalError::InvalidChar(0)));
+        parse_with_default_exponent(u64, 4, "1.5e3", ParseOptions::new(), Err(ParseFixedDecimalError::InvalidChar(3)));
+        parse_with_default_spaces(u64, 4, " 1.25", ParseOptions::new(), Err(ParseFixedDecimalError::InvalidChar(0)));
+        parse_with_default_too_precise(u64, 4, "1.00000", ParseOptions::new(), Err(ParseFixedDecimalError::TooMuchFractionalDigits(4)));
+        parse_with_plus(u64, 4, "+1.50", lenient_options(), Ok(FixedDecimal::from_fractions(15000)));
+        parse_with_neg(i64, 4, "-1.50", lenient_options(), Ok(FixedDecimal::from_fractions(-15000)));
+        parse_with_neg_unsigned(u64, 4, "-1.50", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(0)));
+        parse_with_underscore(u64, 4, "1_000.25", ParseOptions::new().thousands_separator(Some('_')), Ok(FixedDecimal::from_fractions(10002500)));
+        parse_with_comma(u64, 4, "1,000.25", lenient_options(), Ok(FixedDecimal::from_fractions(10002500)));
+        parse_with_comma_groups(u64, 4, "1,000,000", lenient_options(), Ok(FixedDecimal::from_fractions(10000000000)));
+        parse_with_leading_separator(u64, 4, ",100", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(0)));
+        parse_with_trailing_separator(u64, 4, "100,", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(3)));
+        parse_with_double_separator(u64, 4, "1,,000", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(2)));
+        parse_with_separator_before_decimal(u64, 4, "1,.5", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(2)));
+        parse_with_separator_in_fraction(u64, 4, "1.000,5", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(5)));
+        parse_with_european(u64, 4, "1.000,25", ParseOptions::new().thousands_separator(Some('.')).decimal_separator(','), Ok(FixedDecimal::from_fractions(10002500)));
+        parse_with_exponent(u64, 4, "1.5e3", lenient_options(), Ok(FixedDecimal::from_fractions(15000000)));
+        parse_with_exponent_upper(u64, 4, "1.5E+3", lenient_options(), Ok(FixedDecimal::from_fractions(15000000)));
+        parse_with_exponent_neg(u64, 4, "15e-4", lenient_options(), Ok(FixedDecimal::from_fractions(15)));
+        parse_with_exponent_too_precise(u64, 4, "15e-5", lenient_options(), Err(ParseFixedDecimalError::TooMuchFractionalDigits(4)));
+        parse_with_exponent_missing_digits(u64, 4, "1e", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(1)));
+        parse_with_exponent_invalid_digit(u64, 4, "1e1x", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(3)));
+        parse_with_exponent_too_large(u64, 4, "1e100", lenient_options(), Err(ParseFixedDecimalError::TooLarge));
+        parse_with_exponent_huge(u64, 4, "1e99999999999", lenient_options(), Err(ParseFixedDecimalError::TooLarge));
+        parse_with_exponent_huge_neg(u64, 4, "1e-99999999999", lenient_options(), Err(ParseFixedDecimalError::TooMuchFractionalDigits(4)));
+        parse_with_exponent_zero(u64, 4, "0e100", lenient_options(), Ok(FixedDecimal::from_fractions(0)));
+        parse_with_whitespace(u64, 4, "  1.25\t", lenient_options(), Ok(FixedDecimal::from_fractions(12500)));
+        parse_with_whitespace_error_pos(u64, 4, "  1.2x ", lenient_options(), Err(ParseFixedDecimalError::InvalidChar(5)));
+        parse_with_empty(u64, 4, "  ", lenient_options(), Err(ParseFixedDecimalError::NoDigits));
+        parse_with_i16_min(i16, 4, "-3.2768", lenient_options(), Ok(FixedDecimal::from_fractions(-32768)));
+    }
+
+    #[test]
+    fn write_to_buffer_too_small() {
+        let value = FixedDecimal::<u64, 4>::from_fractions(12345);
+        let mut buf = [0u8; 5];
+        assert_eq!(value.write_to(&mut buf), None);
+        let mut buf = [0u8; 6];
+        assert_eq!(value.write_to(&mut buf), Some("1.2345"));
+    }
+
+    #[test]
+    fn display_width_and_alignment() {
+        let value = FixedDecimal::<u64, 0>::from_fractions(12);
+        assert_eq!(format!("[{:5}]", value), "[   12]");
+        assert_eq!(format!("[{:<5}]", value), "[12   ]");
+        assert_eq!(format!("[{:05}]", value), "[00012]");
+        let value = FixedDecimal::<i64, 2>::from_fractions(-150);
+        assert_eq!(format!("[{:8}]", value), "[   -1.50]");
+        assert_eq!(format!("[{:*^9}]", value), "[**-1.50**]");
+        assert_eq!(format!("[{:08}]", value), "[-0001.50]");
+        assert_eq!(format!("[{:+}]", value.checked_neg().unwrap()), "[+1.50]");
+        let value = DynDecimal::<u64>::from_fractions(5, 1);
+        assert_eq!(format!("[{:>4}]", value), "[ 0.5]");
+    }
+
+    #[test]
+    fn display_precision() {
+        let value = FixedDecimal::<i64, 4>::from_fractions(-12000);
+        assert_eq!(format!("{:.2}", value), "-1.20");
+        assert_eq!(format!("{:.1}", value), "-1.2");
+        assert_eq!(format!("{:.6}", value), "-1.200000");
+        assert_eq!(format!("[{:>8.3}]", value), "[  -1.200]");
+        assert_eq!(format!("{:+.1}", value.checked_neg().unwrap()), "+1.2");
+        assert_eq!(
+            format!("{:.0}", FixedDecimal::<u64, 4>::from_fractions(20000)),
+            "2"
+        );
+        // Significant digits are never rounded away
+        let value = FixedDecimal::<i64, 4>::from_fractions(-12345);
+        assert_eq!(format!("{:.2}", value), "-1.2345");
+        assert_eq!(format!("{:.0}", value), "-1.2345");
+        let value = FixedDecimal::<u16, 1>::from_fractions(25);
+        assert_eq!(format!("{:.0}", value), "2.5");
+        let value = DynDecimal::<u64>::from_fractions(5, 1);
+        assert_eq!(format!("{:.2}", value), "0.50");
+        assert_eq!(format!("{:.0}", value), "0.5");
+        let value = DynDecimal::<u64>::from_fractions(5, 0);
+        assert_eq!(format!("{:.2}", value), "5.00");
+    }
+
+    #[test]
+    fn display_u128_max() {
+        let actual = FixedDecimal::<u128, 4>::from_fractions(u128::MAX).to_string();
+        assert_eq!(actual, "34028236692093846346337460743176821.1455");
+    }
+
+    #[test]
+    fn display_i128_min() {
+        let actual = FixedDecimal::<i128, 4>::from_fractions(i128::MIN).to_string();
+        assert_eq!(actual, "-17014118346046923173168730371588410.5728");
+    }
+
+    #[test]
+    fn display_large_precision() {
+        let actual = FixedDecimal::<u64, 80>::from_fractions(12).to_string();
+        let expected = format!("0.{}12", "0".repeat(78));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn fast_path_only_for_u64() {
+        assert!(is_u64::<u64>());
+        assert!(!is_u64::<i64>());
+        assert!(!is_u64::<u32>());
+        assert!(!is_u64::<u128>());
+        assert_eq!(from_u64::<u64>(u64::MAX), Some(u64::MAX));
+        assert_eq!(
+            from_u64::<u64>(0x0102_0304_0506_0708),
+            Some(0x0102_0304_0506_0708)
+        );
+    }
+
+    #[test]
+    fn parse_8_digits_valid() {
+        assert_eq!(parse_8_digits(*b"12345678"), Some(12345678));
+        assert_eq!(parse_8_digits(*b"00000000"), Some(0));
+        assert_eq!(parse_8_digits(*b"99999999"), Some(99999999));
+    }
+
+    #[test]
+    fn parse_8_digits_invalid() {
+        assert_eq!(parse_8_digits(*b"1234567."), None);
+        assert_eq!(parse_8_digits(*b"/2345678"), None);
+        assert_eq!(parse_8_digits(*b"1234:678"), None);
+        assert_eq!(parse_8_digits(*b"1234 678"), None);
+    }
+
+    macro_rules! test_parse_fast_path {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let actual = FixedDecimal::<$typ, $precision>::from_str($input);
+                    let expected = lex_strict::<$typ, $precision>($input);
+                    assert_eq!(actual, expected);
+                }
+            )+
+        };
+    }
+
+    test_parse_fast_path! {
+        parse_fast_u64_4_zero(u64, 4, "0");
+        parse_fast_u64_4_int(u64, 4, "123456789");
+        parse_fast_u64_4_frac(u64, 4, "1.2345");
+        parse_fast_u64_4_short_frac(u64, 4, "1.2");
+        parse_fast_u64_4_no_int(u64, 4, ".5");
+        parse_fast_u64_4_no_frac(u64, 4, "5.");
+        parse_fast_u64_4_dot(u64, 4, ".");
+        parse_fast_u64_4_empty(u64, 4, "");
+        parse_fast_u64_4_max(u64, 4, "1844674407370955.1615");
+        parse_fast_u64_4_max_plus_one(u64, 4, "1844674407370955.1616");
+        parse_fast_u64_4_long_zeros(u64, 4, "00000000000000000000000001.5");
+        parse_fast_u64_4_too_precise(u64, 4, "1.00000");
+        parse_fast_u64_4_two_dots(u64, 4, "1.2.3");
+        parse_fast_u64_4_invalid(u64, 4, "12345678a.5");
+        parse_fast_u64_4_negative(u64, 4, "-1.5");
+        parse_fast_u64_4_plus(u64, 4, "+1.5");
+        parse_fast_u64_19(u64, 19, "1.8446744073709551615");
+        parse_fast_u64_20(u64, 20, "0.18446744073709551615");
+        parse_fast_i16_4_max(i16, 4, "3.2767");
+        parse_fast_i16_4_too_large(i16, 4, "3.2768");
+        parse_fast_i16_4_negative(i16, 4, "-3.2768");
+    }
+
+    type DeError = serde::de::value::Error;
+
+    #[test]
+    fn deserialize_str() {
+        let actual = FixedDecimal::<u64, 4>::deserialize("1.25".into_deserializer());
+        let actual: Result<_, DeError> = actual;
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(12500)));
+    }
+
+    #[test]
+    fn deserialize_u64() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize(12u64.into_deserializer());
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(120000)));
+    }
+
+    #[test]
+    fn deserialize_u64_too_large() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize(u64::MAX.into_deserializer());
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn deserialize_i64() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<i64, 4>::deserialize((-12i64).into_deserializer());
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(-120000)));
+    }
+
+    #[test]
+    fn deserialize_i64_negative_unsigned() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize((-12i64).into_deserializer());
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn deserialize_u128() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u128, 4>::deserialize(u128::from(u64::MAX).into_deserializer());
+        assert_eq!(
+            actual,
+            Ok(FixedDecimal::from_fractions(u128::from(u64::MAX) * 10000))
+        );
+    }
+
+    #[test]
+    fn deserialize_f64_rejected_by_default() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize(1.25f64.into_deserializer());
+        assert!(actual.is_err());
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize_number(1.25f64.into_deserializer());
+        assert!(actual.is_err());
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct JsonAmount {
+        amount: FixedDecimal<u64, 4>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct JsonNumberAmount {
+        #[serde(deserialize_with = "FixedDecimal::deserialize_number")]
+        amount: FixedDecimal<u64, 4>,
+    }
+
+    #[test]
+    fn deserialize_json_derive() {
+        let actual: JsonAmount = serde_json::from_str(r#"{"amount": "1.25"}"#).unwrap();
+        assert_eq!(actual.amount, FixedDecimal::from_fractions(12500));
+        // Numbers are opt-in
+        assert!(serde_json::from_str::<JsonAmount>(r#"{"amount": 12}"#).is_err());
+        let actual: JsonNumberAmount = serde_json::from_str(r#"{"amount": 12}"#).unwrap();
+        assert_eq!(actual.amount, FixedDecimal::from_fractions(120000));
+        let actual: JsonNumberAmount = serde_json::from_str(r#"{"amount": "1.25"}"#).unwrap();
+        assert_eq!(actual.amount, FixedDecimal::from_fractions(12500));
+        assert!(serde_json::from_str::<JsonNumberAmount>(r#"{"amount": 1.25}"#).is_err());
+        assert!(serde_json::from_str::<JsonNumberAmount>(r#"{"amount": -1}"#).is_err());
+    }
+
+    #[test]
+    fn deserialize_str_hint() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize_str("1.25".into_deserializer());
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(12500)));
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize_str(1.25f64.into_deserializer());
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn deserialize_number_u64() {
+        let actual: Result<_, DeError> =
+            FixedDecimal::<u64, 4>::deserialize_number(12u64.into_deserializer());
+        assert_eq!(actual, Ok(FixedDecimal::from_fractions(120000)));
+    }
+
+    macro_rules! test_deserialize_float {
+        ($($name:ident($typ:ty, $precision:literal, $input:expr, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input: f64 = $input;
+                    let actual: Result<_, DeError> =
+                        FixedDecimal::<$typ, $precision>::deserialize_number_or_float(input.into_deserializer());
+                    let expected: Option<FixedDecimal<$typ, $precision>> = $expected;
+                    assert_eq!(actual.ok(), expected);
+                }
+            )+
+        };
+    }
+
+    test_deserialize_float! {
+        deserialize_float_u64_4_exact(u64, 4, 1.25, Some(FixedDecimal::from_fractions(12500)));
+        deserialize_float_u64_4_integral(u64, 4, 12.0, Some(FixedDecimal::from_fractions(120000)));
+        deserialize_float_u64_4_max_digits(u64, 4, 0.0001, Some(FixedDecimal::from_fractions(1)));
+        deserialize_float_u64_4_too_precise(u64, 4, 0.00001, None);
+        deserialize_float_u64_4_inexact_sum(u64, 4, 0.1 + 0.2, None);
+        deserialize_float_u64_4_zero(u64, 4, 0.0, Some(FixedDecimal::from_fractions(0)));
+        deserialize_float_u64_4_neg_zero(u64, 4, -0.0, Some(FixedDecimal::from_fractions(0)));
+        deserialize_float_u64_4_negative(u64, 4, -1.5, None);
+        deserialize_float_u64_4_nan(u64, 4, f64::NAN, None);
+        deserialize_float_u64_4_infinity(u64, 4, f64::INFINITY, None);
+        deserialize_float_u64_4_too_large(u64, 4, 1e30, None);
+        deserialize_float_i64_4_negative(i64, 4, -1.5, Some(FixedDecimal::from_fractions(-15000)));
+    }
+
+    const LIMIT: FixedDecimal<u64, 4> = FixedDecimal::<u64, 4>::from_literal("1000.5");
+
+    #[test]
+    fn from_literal_const() {
+        assert_eq!(LIMIT, FixedDecimal::from_fractions(10005000));
+    }
+
+    #[test]
+    fn fixed_macro() {
+        assert_eq!(
+            crate::fixed!(FixedDecimal<i16, 4>, "-3.2768"),
+            FixedDecimal::from_fractions(-32768)
+        );
+        assert_eq!(
+            crate::fixed!(FixedDecimal<u64, 2>, ".5"),
+            FixedDecimal::from_fractions(50)
+        );
+    }
+
+    macro_rules! test_from_literal_matches_from_str {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let actual = FixedDecimal::<$typ, $precision>::from_literal($input);
+                    let expected = FixedDecimal::<$typ, $precision>::from_str($input).unwrap();
+                    assert_eq!(actual, expected);
+                }
+            )+
+        };
+    }
+
+    test_from_literal_matches_from_str! {
+        from_literal_u64_4_zero(u64, 4, "0");
+        from_literal_u64_4_int(u64, 4, "12");
+        from_literal_u64_4_frac(u64, 4, "1.2345");
+        from_literal_u64_4_trailing_dot(u64, 4, "1.");
+        from_literal_u64_4_max(u64, 4, "1844674407370955.1615");
+        from_literal_i16_4_min(i16, 4, "-3.2768");
+        from_literal_i16_4_max(i16, 4, "3.2767");
+        from_literal_i128_0_min(i128, 0, "-170141183460469231731687303715884105728");
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed decimal literal exceeds the supported precision")]
+    fn from_literal_too_precise() {
+        FixedDecimal::<u64, 4>::from_literal("1.23456");
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed decimal literal is too large")]
+    fn from_literal_too_large() {
+        FixedDecimal::<i16, 4>::from_literal("3.2768");
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed decimal literal is negative but the type is unsigned")]
+    fn from_literal_negative_unsigned() {
+        FixedDecimal::<u64, 4>::from_literal("-1");
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed decimal literal has an invalid character")]
+    fn from_literal_invalid() {
+        FixedDecimal::<u64, 4>::from_literal("1a");
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed decimal literal has no digits")]
+    fn from_literal_empty() {
+        FixedDecimal::<u64, 4>::from_literal("");
+    }
+
+    macro_rules! test_allocate {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal, $weights:expr, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input = FixedDecimal::<$typ, $precision>::from_str($input).unwrap();
+                    let weights: &[$typ] = &$weights;
+                    let actual = input
+                        .allocate(weights)
+                        .map(|parts| parts.iter().map(|p| p.to_string()).collect::<Vec<_>>());
+                    let expected: Option<Vec<&str>> = $expected;
+                    assert_eq!(actual, expected.map(|e| e.into_iter().map(String::from).collect::<Vec<_>>()));
+                }
+            )+
+        };
+    }
+
+    test_allocate! {
+        allocate_u64_2_thirds(u64, 2, "1.00", [1, 1, 1], Some(vec!["0.34", "0.33", "0.33"]));
+        allocate_u64_2_exact(u64, 2, "10.00", [1, 3], Some(vec!["2.50", "7.50"]));
+        allocate_u64_2_largest_remainder(u64, 2, "0.05", [2, 7], Some(vec!["0.01", "0.04"]));
+        allocate_u64_2_zero_weight(u64, 2, "1.00", [0, 1, 1], Some(vec!["0.00", "0.50", "0.50"]));
+        allocate_u64_2_zero_amount(u64, 2, "0", [1, 2], Some(vec!["0.00", "0.00"]));
+        allocate_u64_2_tie(u64, 2, "0.05", [3, 7], Some(vec!["0.02", "0.03"]));
+        allocate_u64_2_no_weights(u64, 2, "1.00", [], None);
+        allocate_u64_2_all_zero(u64, 2, "1.00", [0, 0], None);
+        allocate_u64_4_large(u64, 4, "1844674407370955.1615", [1, 1], Some(vec!["922337203685477.5808", "922337203685477.5807"]));
+        allocate_i64_2_negative(i64, 2, "-1.00", [1, 1, 1], Some(vec!["-0.34", "-0.33", "-0.33"]));
+        allocate_i64_2_negative_weight(i64, 2, "1.00", [1, -1], None);
+    }
+
+    #[test]
+    fn allocate_by_decimal_weights() {
+        let input = FixedDecimal::<u64, 2>::from_literal("100.00");
+        let weights = [
+            FixedDecimal::<u64, 4>::from_literal("0.3333"),
+            FixedDecimal::<u64, 4>::from_literal("0.6667"),
+        ];
+        let actual = input.allocate_by(&weights);
+        let expected = vec![
+            FixedDecimal::<u64, 2>::from_literal("33.33"),
+            FixedDecimal::<u64, 2>::from_literal("66.67"),
+        ];
+        assert_eq!(actual, Some(expected));
+    }
+
+    #[test]
+    fn split_sums_to_total() {
+        let input = FixedDecimal::<u64, 4>::from_literal("10.0001");
+        let parts = input.split(7).unwrap();
+        assert_eq!(parts.len(), 7);
+        let total = parts
+            .iter()
+            .try_fold(FixedDecimal::from_fractions(0), |acc, p| acc.checked_add(p));
+        assert_eq!(total, Some(input));
+    }
+
+    #[test]
+    fn split_zero_parts() {
+        let input = FixedDecimal::<u64, 4>::from_literal("1");
+        assert_eq!(input.split(0), None);
+    }
+
+    #[test]
+    fn checked_neg_and_abs() {
+        let value = FixedDecimal::<i16, 4>::from_literal("-1.5");
+        assert_eq!(
+            value.checked_neg(),
+            Some(FixedDecimal::from_fractions(15000))
+        );
+        assert_eq!(
+            value.checked_abs(),
+            Some(FixedDecimal::from_fractions(15000))
+        );
+        let min = FixedDecimal::<i16, 4>::from_fractions(i16::MIN);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(min.checked_abs(), None);
+        let unsigned = FixedDecimal::<u64, 4>::from_fractions(1);
+        assert_eq!(unsigned.checked_neg(), None);
+        assert_eq!(unsigned.checked_abs(), Some(unsigned));
+    }
+
+    #[test]
+    fn dyn_decimal_from_fixed() {
+        let actual = DynDecimal::from(FixedDecimal::<u64, 2>::from_literal("12.34"));
+        assert_eq!(actual, DynDecimal::from_fractions(1234, 2));
+        assert_eq!(actual.to_string(), "12.34");
+    }
+
+    #[test]
+    fn dyn_decimal_to_fixed() {
+        let input = DynDecimal::<u64>::from_fractions(1234, 2);
+        assert_eq!(
+            FixedDecimal::<u64, 2>::try_from(input),
+            Ok(FixedDecimal::from_fractions(1234))
+        );
+        assert_eq!(
+            FixedDecimal::<u64, 4>::try_from(input),
+            Err(DynDecimalError::PrecisionMismatch { left: 2, right: 4 })
+        );
+        assert_eq!(
+            input.to_fixed::<4>(),
+            Some(FixedDecimal::from_fractions(123400))
+        );
+        assert_eq!(input.to_fixed::<1>(), None);
+    }
+
+    #[test]
+    fn dyn_decimal_add_precision_mismatch() {
+        let jpy = DynDecimal::<u64>::parse("100", 0).unwrap();
+        let usd = DynDecimal::<u64>::parse("1.25", 2).unwrap();
+        assert_eq!(
+            jpy.checked_add(&usd),
+            Err(DynDecimalError::PrecisionMismatch { left: 0, right: 2 })
+        );
+        assert_eq!(
+            jpy.rescale(2).unwrap().checked_add(&usd),
+            Ok(DynDecimal::from_fractions(10125, 2))
+        );
+    }
+
+    #[test]
+    fn dyn_decimal_sub_overflow() {
+        let one = DynDecimal::<u64>::parse("1", 8).unwrap();
+        let two = DynDecimal::<u64>::parse("2", 8).unwrap();
+        assert_eq!(one.checked_sub(&two), Err(DynDecimalError::Overflow));
+    }
+
+    #[test]
+    fn dyn_decimal_parse() {
+        assert_eq!(
+            DynDecimal::<i64>::parse("-0.00000001", 8),
+            Ok(DynDecimal::from_fractions(-1, 8))
+        );
+        assert_eq!(
+            DynDecimal::<i64>::parse("0.5", 0),
+            Err(ParseFixedDecimalError::TooMuchFractionalDigits(0))
+        );
+    }
+
+    #[test]
+    fn dyn_decimal_rescale_with() {
+        let input = DynDecimal::<u64>::parse("1.005", 3).unwrap();
+        assert_eq!(input.rescale(2), Err(ArithmeticError::Inexact));
+        assert_eq!(
+            input.rescale_with(2, RoundingMode::HalfEven),
+            Ok(DynDecimal::from_fractions(100, 2))
+        );
+    }
+
+    #[test]
+    fn checked_sum_empty() {
+        let values: Vec<FixedDecimal<u64, 4>> = Vec::new();
+        let actual: Option<FixedDecimal<u64, 4>> = values.into_iter().checked_sum();
+        assert_eq!(actual, Some(FixedDecimal::from_fractions(0)));
+    }
+
+    #[test]
+    fn checked_sum_overflow() {
+        let values = [
+            FixedDecimal::<u64, 4>::from_fractions(u64::MAX),
+            FixedDecimal::<u64, 4>::from_fractions(1),
+        ];
+        let actual: Option<FixedDecimal<u64, 4>> = values.iter().checked_sum();
+        assert_eq!(actual, None);
+        let actual = FixedDecimal::<u128, 4>::checked_sum_widening(values.iter().copied());
+        assert_eq!(
+            actual,
+            Some(FixedDecimal::from_fractions(u128::from(u64::MAX) + 1))
+        );
+    }
+
+    #[test]
+    fn checked_product() {
+        let values = [
+            FixedDecimal::<u64, 2>::from_literal("1.50"),
+            FixedDecimal::<u64, 2>::from_literal("2.00"),
+            FixedDecimal::<u64, 2>::from_literal("0.10"),
+        ];
+        let actual: Option<FixedDecimal<u64, 2>> = values.iter().checked_product();
+        assert_eq!(actual, Some(FixedDecimal::<u64, 2>::from_literal("0.30")));
+        // `0.30 * 0.05 = 0.015` would require rounding
+        let inexact = values
+            .iter()
+            .copied()
+            .chain(std::iter::once(FixedDecimal::<u64, 2>::from_literal(
+                "0.05",
+            )))
+            .checked_product::<FixedDecimal<u64, 2>>();
+        assert_eq!(inexact, None);
+    }
+
+    macro_rules! test_try_from_f64 {
+        ($($name:ident($typ:ty, $precision:literal, $input:expr, $mode:ident, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input: f64 = $input;
+                    let actual = FixedDecimal::<$typ, $precision>::try_from_f64(input, RoundingMode::$mode)
+                        .map(|r| r.to_string());
+                    let expected: Result<&str, FromF64Error> = $expected;
+                    assert_eq!(actual.as_deref().map_err(|e| *e), expected);
+                }
+            )+
+        };
+    }
+
+    test_try_from_f64! {
+        from_f64_u64_4_exact(u64, 4, 1.25, Exact, Ok("1.2500"));
+        from_f64_u64_4_integral(u64, 4, 12.0, Exact, Ok("12.0000"));
+        from_f64_u64_4_zero(u64, 4, 0.0, Exact, Ok("0.0000"));
+        from_f64_u64_4_neg_zero(u64, 4, -0.0, Exact, Ok("0.0000"));
+        from_f64_u64_4_tenth_exact(u64, 4, 0.1, Exact, Err(FromF64Error::Inexact));
+        from_f64_u64_4_tenth_half_even(u64, 4, 0.1, HalfEven, Ok("0.1000"));
+        from_f64_u64_4_tenth_ceiling(u64, 4, 0.1, Ceiling, Ok("0.1001"));
+        from_f64_u64_4_tenth_floor(u64, 4, 0.1, Floor, Ok("0.1000"));
+        from_f64_u64_4_subnormal_floor(u64, 4, f64::MIN_POSITIVE / 2.0, Floor, Ok("0.0000"));
+        from_f64_u64_4_subnormal_ceiling(u64, 4, f64::MIN_POSITIVE / 2.0, Ceiling, Ok("0.0001"));
+        from_f64_u64_4_nan(u64, 4, f64::NAN, HalfEven, Err(FromF64Error::NotFinite));
+        from_f64_u64_4_infinity(u64, 4, f64::INFINITY, HalfEven, Err(FromF64Error::NotFinite));
+        from_f64_u64_4_too_large(u64, 4, 1e30, HalfEven, Err(FromF64Error::OutOfRange));
+        from_f64_u64_4_negative(u64, 4, -1.5, HalfEven, Err(FromF64Error::OutOfRange));
+        from_f64_u64_4_tiny_negative(u64, 4, -0.00001, TowardZero, Ok("0.0000"));
+        from_f64_i64_4_negative(i64, 4, -1.5, Exact, Ok("-1.5000"));
+        from_f64_i64_4_neg_tenth_floor(i64, 4, -0.1, Floor, Ok("-0.1001"));
+        from_f64_i64_0_min(i64, 0, -9223372036854775808.0, Exact, Ok("-9223372036854775808"));
+        from_f64_i64_0_max(i64, 0, 9223372036854775808.0, Exact, Err(FromF64Error::OutOfRange));
+        from_f64_u128_4_large(u128, 4, 1e30, Exact, Ok("1000000000000000019884624838656.0000"));
+    }
+
+    #[test]
+    fn to_f64_lossy() {
+        assert_eq!(
+            FixedDecimal::<u64, 4>::from_literal("0.1").to_f64_lossy(),
+            0.1
+        );
+        assert_eq!(
+            FixedDecimal::<i64, 4>::from_literal("-1.25").to_f64_lossy(),
+            -1.25
+        );
+        let max = FixedDecimal::<u64, 4>::from_fractions(u64::MAX).to_f64_lossy();
+        assert_eq!(max, 1844674407370955.2);
+    }
+
+    macro_rules! test_format_with {
+        ($($name:ident($typ:ty, $precision:literal, $input:literal, $format:expr, $expected:expr));+$(;)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let input = FixedDecimal::<$typ, $precision>::from_str($input).unwrap();
+                    let format: FixedDecimalFormat = $format;
+                    let actual = input.format_with(&format).map(|r| r.to_string());
+                    let expected: Result<&str, ArithmeticError> = $expected;
+                    assert_eq!(actual.as_deref().map_err(|e| *e), expected);
+                }
+            )+
+        };
+    }
+
+    test_format_with! {
+        format_default(u64, 4, "1234.5", FixedDecimalFormat::new(), Ok("1234.5000"));
+        format_default_precision_0(u64, 0, "1234", FixedDecimalFormat::new(), Ok("1234"));
+        format_trim(u64, 4, "1234.5", FixedDecimalFormat::new().trim_trailing_zeros(true), Ok("1234.5"));
+        format_trim_integral(u64, 4, "1234", FixedDecimalFormat::new().trim_trailing_zeros(true), Ok("1234"));
+        format_trim_min(u64, 4, "1234", FixedDecimalFormat::new().trim_trailing_zeros(true).min_fractional_digits(2), Ok("1234.00"));
+        format_min_above_precision(u64, 2, "1.5", FixedDecimalFormat::new().min_fractional_digits(4), Ok("1.5000"));
+        format_max_exact(u64, 4, "1.2500", FixedDecimalFormat::new().max_fractional_digits(2, RoundingMode::Exact), Ok("1.25"));
+        format_max_inexact(u64, 4, "1.2345", FixedDecimalFormat::new().max_fractional_digits(2, RoundingMode::Exact), Err(ArithmeticError::Inexact));
+        format_max_half_even(u64, 4, "1.2350", FixedDecimalFormat::new().max_fractional_digits(2, RoundingMode::HalfEven), Ok("1.24"));
+        format_max_zero(u64, 4, "1.5", FixedDecimalFormat::new().max_fractional_digits(0, RoundingMode::HalfEven), Ok("2"));
+        format_grouping(u64, 2, "1234567.89", FixedDecimalFormat::new().thousands_separator(Some(',')), Ok("1,234,567.89"));
+        format_grouping_short(u64, 2, "123.45", FixedDecimalFormat::new().thousands_separator(Some(',')), Ok("123.45"));
+        format_grouping_negative(i64, 2, "-1234.5", FixedDecimalFormat::new().thousands_separator(Some(',')), Ok("-1,234.50"));
+        format_european(u64, 2, "1234567.89", FixedDecimalFormat::new().thousands_separator(Some('.')).decimal_separator(','), Ok("1.234.567,89"));
+    }
+
+    #[test]
+    fn format_width_and_alignment() {
+        let value = FixedDecimal::<i64, 2>::from_literal("-1.5");
+        let formatted = value.format_with(&FixedDecimalFormat::new()).unwrap();
+        assert_eq!(format!("{:8}", formatted), "   -1.50");
+        assert_eq!(format!("{:<8}|", formatted), "-1.50   |");
+        assert_eq!(format!("{:*^9}", formatted), "**-1.50**");
+        assert_eq!(format!("{:2}", formatted), "-1.50");
+    }
+
+    #[test]
+    fn format_dyn_decimal() {
+        let value = DynDecimal::<u64>::parse("1000", 0).unwrap();
+        let format = FixedDecimalFormat::new()
+            .thousands_separator(Some(' '))
+            .min_fractional_digits(2);
+        assert_eq!(value.format_with(&format).unwrap().as_str(), "1 000.00");
+    }
+
+    mod big_int {
+        use crate::fixed_decimal::{FixedDecimal, RoundingMode};
+        use num::BigInt;
+        use serde::de::IntoDeserializer;
+        use serde::Deserialize;
+        use std::str::FromStr;
+
+        type BigDecimal = FixedDecimal<BigInt, 4>;
+
+        fn big(s: &str) -> BigDecimal {
+            BigDecimal::from_str(s).unwrap()
+        }
+
+        #[test]
+        fn parse_and_display() {
+            let input = "-123456789012345678901234567890123456789012345.6789";
+            let actual = big(input);
+            assert_eq!(
+                actual.fractions(),
+                &BigInt::from_str("-1234567890123456789012345678901234567890123456789").unwrap()
+            );
+            assert_eq!(actual.to_string(), input);
+            assert_eq!(big("0.5").to_string(), "0.5000");
+        }
+
+        #[test]
+        fn display_very_large() {
+            let input = format!("{}.0001", "9".repeat(200));
+            assert_eq!(big(&input).to_string(), input);
+        }
+
+        #[test]
+        fn parse_too_precise() {
+            assert!(BigDecimal::from_str("1.00001").is_err());
+        }
+
+        #[test]
+        fn arithmetic() {
+            let max = format!("{}.9999", "9".repeat(40));
+            let sum = big(&max).checked_add(&big("0.0001")).unwrap();
+            assert_eq!(sum.to_string(), format!("1{}.0000", "0".repeat(40)));
+            let product = big("1.5")
+                .checked_mul(
+                    &FixedDecimal::<BigInt, 2>::from_str("0.33").unwrap(),
+                    RoundingMode::HalfEven,
+                )
+                .unwrap();
+            assert_eq!(product.to_string(), "0.4950");
+            let quotient = big("1").checked_div(&big("3"), RoundingMode::Exact);
+            assert!(quotient.is_err());
+            let quotient = big("-1")
+                .checked_div(&big("3"), RoundingMode::Floor)
+                .unwrap();
+            assert_eq!(quotient.to_string(), "-0.3334");
+            let parts = big("1").split(3).unwrap();
+            let parts: Vec<String> = parts.iter().map(|p| p.to_string()).collect();
+            assert_eq!(parts, vec!["0.3334", "0.3333", "0.3333"]);
+        }
+
+        #[test]
+        fn deserialize() {
+            let actual: Result<_, serde::de::value::Error> =
+                BigDecimal::deserialize("12.5".into_deserializer());
+            assert_eq!(actual, Ok(big("12.5")));
+            let actual: Result<_, serde::de::value::Error> =
+                BigDecimal::deserialize(u128::MAX.into_deserializer());
+            assert_eq!(actual, Ok(big(&u128::MAX.to_string())));
+        }
+
+        #[test]
+        fn serialize() {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer.serialize(big("-1.25")).unwrap();
+            let actual = writer.into_inner().unwrap();
+            assert_eq!(std::str::from_utf8(&actual), Ok("-1.2500\n"));
+        }
+    }
+
+    mod wide {
+        use crate::fixed_decimal::{FixedDecimal, RoundingMode};
+        use serde::de::IntoDeserializer;
+        use serde::Deserialize;
+        use std::str::FromStr;
+
+        #[test]
+        fn u128_parse_max() {
+            let input = "34028236692093846346337460743176821.1455";
+            let actual = FixedDecimal::<u128, 4>::from_str(input);
+            assert_eq!(actual, Ok(FixedDecimal::from_fractions(u128::MAX)));
+            let actual =
+                FixedDecimal::<u128, 4>::from_str("34028236692093846346337460743176821.1456");
+            assert!(actual.is_err());
+        }
+
+        #[test]
+        fn i128_parse_min() {
+            let input = "-17014118346046923173168730371588410.5728";
+            let actual = FixedDecimal::<i128, 4>::from_str(input);
+            assert_eq!(actual, Ok(FixedDecimal::from_fractions(i128::MIN)));
+        }
+
+        #[test]
+        fn u128_arithmetic() {
+            let max = FixedDecimal::<u128, 4>::from_fractions(u128::MAX);
+            let one = FixedDecimal::<u128, 4>::from_literal("0.0001");
+            assert_eq!(max.checked_add(&one), None);
+            let half = max.checked_div_int(&2, RoundingMode::TowardZero).unwrap();
+            assert_eq!(half, FixedDecimal::from_fractions(u128::MAX / 2));
+            let factor = FixedDecimal::<u128, 2>::from_literal("1.00");
+            assert_eq!(max.checked_mul(&factor, RoundingMode::Exact), Ok(max));
+        }
+
+        #[test]
+        fn i128_arithmetic() {
+            let min = FixedDecimal::<i128, 4>::from_fractions(i128::MIN);
+            assert_eq!(min.checked_neg(), None);
+            let neg_one = FixedDecimal::<i128, 0>::from_literal("-1");
+            assert!(min.checked_div(&neg_one, RoundingMode::Exact).is_err());
+            let value = FixedDecimal::<i128, 4>::from_literal("-2.5");
+            assert_eq!(
+                value.checked_mul(
+                    &FixedDecimal::<i128, 1>::from_literal("0.5"),
+                    RoundingMode::HalfEven
+                ),
+                Ok(FixedDecimal::<i128, 4>::from_literal("-1.25"))
+            );
+        }
+
+        #[test]
+        fn u128_deserialize() {
+            let actual: Result<_, serde::de::value::Error> = FixedDecimal::<u128, 4>::deserialize(
+                "34028236692093846346337460743176821.1455".into_deserializer(),
+            );
+            assert_eq!(actual, Ok(FixedDecimal::from_fractions(u128::MAX)));
+        }
+
+        #[test]
+        fn i128_deserialize() {
+            let actual: Result<_, serde::de::value::Error> =
+                FixedDecimal::<i128, 4>::deserialize(i128::MIN.into_deserializer());
+            assert!(actual.is_err());
+            let actual: Result<_, serde::de::value::Error> =
+                FixedDecimal::<i128, 4>::deserialize((-5i128).into_deserializer());
+            assert_eq!(actual, Ok(FixedDecimal::from_fractions(-50000)));
+        }
+    }
 }
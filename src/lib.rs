@@ -1,5 +1,7 @@
 pub mod account_service;
+pub mod binary;
 pub mod cli;
 pub mod core;
 pub mod csv;
+pub mod fee;
 pub mod fixed_decimal;
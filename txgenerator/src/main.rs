@@ -6,14 +6,14 @@ use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
-use txdemo::core::fixed_decimal::FixedDecimal;
 use txdemo::core::{cmd, Transaction, UnsignedAssetCount};
-use txdemo::core::{ClientId, Command, TransactionId, TransactionMeta};
+use txdemo::core::{AssetId, ClientId, Command, TransactionId, TransactionMeta};
+use txdemo::fixed_decimal::FixedDecimal;
 
 // Number of commands to generate
 const CMD_COUNT: u32 = 1100000;
 // Max number of clients
-const CLIENT_COUNT: u16 = 100;
+const CLIENT_COUNT: u64 = 100;
 // Max number of transactions
 const TX_COUNT: u32 = 1000000;
 // Probability that the transaction id will be picked sequentially
@@ -85,6 +85,10 @@ fn main() {
                     chargeback_count += 1;
                     writeln!(file, "chargeback, {}, {},", cmd.client, cmd.client)
                 }
+                Command::Transfer(_) => unreachable!("the generator does not emit transfers"),
+                Command::Unlock(_) | Command::Freeze(_) | Command::Close(_) | Command::Fee(_) => {
+                    unreachable!("the generator does not emit operator commands")
+                }
             };
             write_res.unwrap();
         }
@@ -138,18 +142,32 @@ impl<Rng: rand::Rng> CommandGenerator<Rng> {
         // Use exponential distribution for deposits and uniform for withdrawals
         // They have the same average here, but overall the difference tends to grow
         if self.rng.gen_bool(DEPOSIT_PROBA) {
-            let amount = 1.0 + self.rng.sample(&self.deposit_amount_distr).round();
+            let amount = 1.0 + self.rng.sample(self.deposit_amount_distr).round();
             let amount = if ((amount as u64) as f64) == amount {
                 amount as u64
             } else {
                 0
             };
-            let amount = UnsignedAssetCount::new(FixedDecimal::from_fractions(amount));
-            Transaction::Deposit(TransactionMeta { id, client, amount })
+            let amount =
+                UnsignedAssetCount::new(FixedDecimal::<u64, 4>::from_fractions(amount).cast());
+            Transaction::Deposit(TransactionMeta {
+                id,
+                client,
+                asset: AssetId::DEFAULT,
+                amount,
+                timestamp: None,
+            })
         } else {
             let amount = self.rng.gen_range(0..=2000000);
-            let amount = UnsignedAssetCount::new(FixedDecimal::from_fractions(amount));
-            Transaction::Withdrawal(TransactionMeta { id, client, amount })
+            let amount =
+                UnsignedAssetCount::new(FixedDecimal::<u64, 4>::from_fractions(amount).cast());
+            Transaction::Withdrawal(TransactionMeta {
+                id,
+                client,
+                asset: AssetId::DEFAULT,
+                amount,
+                timestamp: None,
+            })
         }
     }
 
@@ -159,7 +177,12 @@ impl<Rng: rand::Rng> CommandGenerator<Rng> {
         if self.rng.gen_bool(PROBA_DISPUTE_CHANGE_STATE) && Some((client, tx)) == base {
             self.open_disputes.push((client, tx));
         }
-        cmd::Dispute { client, tx }
+        cmd::Dispute {
+            client,
+            tx,
+            amount: None,
+            timestamp: None,
+        }
     }
 
     fn gen_resolve(&mut self) -> cmd::Resolve {
@@ -168,7 +191,12 @@ impl<Rng: rand::Rng> CommandGenerator<Rng> {
         if self.rng.gen_bool(PROBA_DISPUTE_CHANGE_STATE) && Some((client, tx)) == base {
             self.disputable_tx.push((client, tx));
         }
-        cmd::Resolve { client, tx }
+        cmd::Resolve {
+            client,
+            tx,
+            amount: None,
+            timestamp: None,
+        }
     }
 
     fn gen_chargeback(&mut self) -> cmd::Chargeback {
@@ -177,7 +205,12 @@ impl<Rng: rand::Rng> CommandGenerator<Rng> {
         if self.rng.gen_bool(PROBA_DISPUTE_CHANGE_STATE) && Some((client, tx)) == base {
             self.disputable_tx.push((client, tx));
         }
-        cmd::Chargeback { client, tx }
+        cmd::Chargeback {
+            client,
+            tx,
+            amount: None,
+            timestamp: None,
+        }
     }
 
     fn derive_dispute(
@@ -232,6 +265,7 @@ impl<Rng: rand::Rng> Iterator for CommandGenerator<Rng> {
             match self.gen_tx() {
                 Transaction::Deposit(tx) => Command::Deposit(cmd::Deposit(tx)),
                 Transaction::Withdrawal(tx) => Command::Withdrawal(cmd::Withdrawal(tx)),
+                Transaction::Transfer { meta, to } => Command::Transfer(cmd::Transfer { meta, to }),
             }
         } else if self.rng.gen_bool(NEW_DISPUTE_PROBA) {
             Command::Dispute(self.gen_dispute())